@@ -0,0 +1,20 @@
+-- Extensions used for fuzzy license plate matching
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS fuzzystrmatch;
+
+-- Strip separators and upper-case a license plate ('abc-123' -> 'ABC123')
+CREATE OR REPLACE FUNCTION canonical_plate(plate TEXT)
+RETURNS TEXT AS $$
+    SELECT UPPER(regexp_replace(plate, '[^A-Za-z0-9]', '', 'g'));
+$$ LANGUAGE sql IMMUTABLE STRICT;
+
+-- Collapse characters riders commonly confuse when reading a plate
+-- (O/Q -> 0, I/L -> 1, Z -> 2, S -> 5, G -> 6, B -> 8)
+CREATE OR REPLACE FUNCTION normalize_plate(plate TEXT)
+RETURNS TEXT AS $$
+    SELECT translate(canonical_plate(plate), 'OQILZSGB', '00112568');
+$$ LANGUAGE sql IMMUTABLE STRICT;
+
+-- Create indexes
+CREATE INDEX idx_drivers_canonical_plate ON drivers(canonical_plate(license_plate));
+CREATE INDEX idx_drivers_normalized_plate_trgm ON drivers USING GIN (normalize_plate(license_plate) gin_trgm_ops);
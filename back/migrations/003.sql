@@ -0,0 +1,20 @@
+-- Create an enum for complaint categories
+CREATE TYPE complaint_category AS ENUM (
+    'harassment',
+    'overcharging',
+    'reckless_driving',
+    'route_deviation',
+    'theft',
+    'assault',
+    'other'
+);
+
+-- Category and reported incident time for complaints
+ALTER TABLE complaints
+    ADD COLUMN category complaint_category NOT NULL DEFAULT 'other',
+    ADD COLUMN incident_at TIMESTAMP WITH TIME ZONE;
+
+-- Create indexes
+CREATE INDEX idx_complaints_category ON complaints(category);
+CREATE INDEX idx_complaints_taxi_application ON complaints(taxi_application);
+CREATE INDEX idx_complaints_incident_at ON complaints(COALESCE(incident_at, created_at));
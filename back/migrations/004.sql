@@ -0,0 +1,13 @@
+-- Rank complaint categories by how serious the reported behaviour is (higher is worse)
+CREATE OR REPLACE FUNCTION complaint_severity(category complaint_category)
+RETURNS INTEGER AS $$
+    SELECT CASE category
+        WHEN 'assault' THEN 5
+        WHEN 'harassment' THEN 4
+        WHEN 'theft' THEN 4
+        WHEN 'reckless_driving' THEN 3
+        WHEN 'overcharging' THEN 2
+        WHEN 'route_deviation' THEN 2
+        ELSE 1
+    END;
+$$ LANGUAGE sql IMMUTABLE STRICT;
@@ -0,0 +1,15 @@
+-- Users allowed to moderate complaints, keyed by auth user id
+CREATE TABLE moderators (
+    user_id TEXT PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create trigger for moderators
+CREATE TRIGGER set_moderators_created_at
+BEFORE INSERT ON moderators
+FOR EACH ROW
+EXECUTE FUNCTION set_created_at();
+
+-- Create indexes for keyset pagination over complaints
+CREATE INDEX idx_complaints_driver_id_created_at ON complaints(driver_id, created_at DESC, id DESC);
+CREATE INDEX idx_complaints_published_created_at ON complaints(published, created_at, id);
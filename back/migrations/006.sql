@@ -0,0 +1,25 @@
+-- Store bucket object keys instead of absolute URLs, plus object metadata
+ALTER TABLE driver_images RENAME COLUMN image_url TO image_key;
+ALTER TABLE driver_images
+    ALTER COLUMN image_key TYPE VARCHAR(1024),
+    ADD COLUMN content_type VARCHAR(255),
+    ADD COLUMN size_bytes BIGINT,
+    ADD COLUMN sha256 CHAR(64);
+
+ALTER TABLE complaint_images RENAME COLUMN image_url TO image_key;
+ALTER TABLE complaint_images
+    ALTER COLUMN image_key TYPE VARCHAR(1024),
+    ADD COLUMN content_type VARCHAR(255),
+    ADD COLUMN size_bytes BIGINT,
+    ADD COLUMN sha256 CHAR(64);
+
+-- Convert existing URLs to keys. Images were stored as virtual-hosted S3 URLs
+-- (https://{bucket}.s3.amazonaws.com/{key}) or local storage URLs
+-- (http://{host}/api/storage/{key}), so the key is the path without those prefixes.
+UPDATE driver_images
+SET image_key = regexp_replace(regexp_replace(image_key, '^https?://[^/]+/', ''), '^api/storage/', '')
+WHERE image_key ~ '^https?://';
+
+UPDATE complaint_images
+SET image_key = regexp_replace(regexp_replace(image_key, '^https?://[^/]+/', ''), '^api/storage/', '')
+WHERE image_key ~ '^https?://';
@@ -0,0 +1,20 @@
+-- Object keys handed out by generate-image-upload-url. Only these keys can be
+-- attached to drivers and complaints, and each one only once.
+CREATE TABLE image_uploads (
+    image_key VARCHAR(1024) PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    attached_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Create trigger for image_uploads
+CREATE TRIGGER set_image_uploads_created_at
+BEFORE INSERT ON image_uploads
+FOR EACH ROW
+EXECUTE FUNCTION set_created_at();
+
+-- Images attached before uploads were tracked count as issued and attached
+INSERT INTO image_uploads (image_key, attached_at)
+SELECT image_key, CURRENT_TIMESTAMP FROM driver_images
+UNION
+SELECT image_key, CURRENT_TIMESTAMP FROM complaint_images
+ON CONFLICT (image_key) DO NOTHING;
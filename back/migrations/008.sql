@@ -0,0 +1,26 @@
+-- Images go through a background pipeline (validation, EXIF stripping, WebP
+-- re-encoding, thumbnails) before they are shown
+CREATE TYPE image_processing_status AS ENUM ('pending', 'processing', 'ready', 'failed');
+
+ALTER TABLE driver_images
+    ADD COLUMN thumbnail_key VARCHAR(1024),
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER,
+    ADD COLUMN processing_status image_processing_status NOT NULL DEFAULT 'pending',
+    ADD COLUMN processing_started_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN processing_error TEXT;
+
+ALTER TABLE complaint_images
+    ADD COLUMN thumbnail_key VARCHAR(1024),
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER,
+    ADD COLUMN processing_status image_processing_status NOT NULL DEFAULT 'pending',
+    ADD COLUMN processing_started_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN processing_error TEXT;
+
+-- Existing images were stored as uploaded, so the 'pending' default above
+-- queues them for processing like new ones
+
+-- Create indexes for the processing queue
+CREATE INDEX idx_driver_images_pending ON driver_images(id) WHERE processing_status IN ('pending', 'processing');
+CREATE INDEX idx_complaint_images_pending ON complaint_images(id) WHERE processing_status IN ('pending', 'processing');
@@ -0,0 +1,7 @@
+-- Moderator redactions of complaint evidence. The public image_key then points
+-- to a redacted copy while the original is kept under a private key.
+ALTER TABLE complaint_images
+    ADD COLUMN original_key VARCHAR(1024),
+    ADD COLUMN redactions JSONB,
+    ADD COLUMN redacted_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN redacted_by TEXT;
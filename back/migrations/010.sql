@@ -0,0 +1,5 @@
+-- Perceptual hashes of processed images, to spot the same photo being reused
+-- across complaints and drivers. Images processed before this migration have
+-- no hash and are left out of comparisons.
+ALTER TABLE driver_images ADD COLUMN perceptual_hash BIGINT;
+ALTER TABLE complaint_images ADD COLUMN perceptual_hash BIGINT;
@@ -0,0 +1,25 @@
+-- Complaints find their driver by exact name and plate, creating it when
+-- missing. Concurrent complaints could both miss it and create it twice, so
+-- merge existing duplicates into the oldest row and let the database enforce
+-- uniqueness from now on.
+CREATE TEMPORARY TABLE duplicate_drivers AS
+SELECT id, keep_id
+FROM (
+    SELECT id, MIN(id) OVER (PARTITION BY name, license_plate) AS keep_id
+    FROM drivers
+) drivers_by_identity
+WHERE id <> keep_id;
+
+UPDATE complaints c SET driver_id = d.keep_id
+FROM duplicate_drivers d
+WHERE c.driver_id = d.id;
+
+UPDATE driver_images i SET driver_id = d.keep_id
+FROM duplicate_drivers d
+WHERE i.driver_id = d.id;
+
+DELETE FROM drivers WHERE id IN (SELECT id FROM duplicate_drivers);
+
+DROP TABLE duplicate_drivers;
+
+ALTER TABLE drivers ADD CONSTRAINT drivers_name_license_plate_key UNIQUE (name, license_plate);
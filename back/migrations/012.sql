@@ -0,0 +1,15 @@
+-- Idempotency keys sent with complaint submissions, so a retried request gets
+-- the original response instead of creating the complaint again. The response
+-- is stored in the same transaction as the complaint.
+CREATE TABLE idempotency_keys (
+    idempotency_key VARCHAR(255) PRIMARY KEY,
+    request_hash CHAR(64) NOT NULL,
+    response JSONB,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create trigger for idempotency_keys
+CREATE TRIGGER set_idempotency_keys_created_at
+BEFORE INSERT ON idempotency_keys
+FOR EACH ROW
+EXECUTE FUNCTION set_created_at();
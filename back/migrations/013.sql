@@ -0,0 +1,6 @@
+-- Rate limit counters, one per client and endpoint, restarted at each window
+CREATE TABLE rate_limit_counters (
+    counter_key VARCHAR(255) PRIMARY KEY,
+    window_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    hits INTEGER NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Complaints resembling an earlier complaint against the same driver are
+-- linked to it, so moderators can review them as likely resubmissions
+ALTER TABLE complaints ADD COLUMN duplicate_of_id INTEGER REFERENCES complaints(id) ON DELETE SET NULL;
+CREATE INDEX idx_complaints_duplicate_of_id ON complaints(duplicate_of_id);
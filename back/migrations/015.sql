@@ -0,0 +1,14 @@
+-- Kinds of personal data masked in complaint descriptions
+CREATE TYPE pii_kind AS ENUM (
+    'email',
+    'phone',
+    'dni',
+    'curp',
+    'address'
+);
+
+-- Descriptions are stored with personal data masked. The text as submitted
+-- is kept for moderators, along with the kinds of data that were found.
+ALTER TABLE complaints
+    ADD COLUMN original_description TEXT,
+    ADD COLUMN pii_kinds pii_kind[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,8 @@
+-- Profanity found in a complaint. Mild terms are masked in its description,
+-- severe ones are left for moderators to review.
+CREATE TYPE profanity_severity AS ENUM (
+    'mild',
+    'severe'
+);
+
+ALTER TABLE complaints ADD COLUMN profanity_severity profanity_severity;
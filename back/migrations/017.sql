@@ -0,0 +1,57 @@
+-- Reporters moderators trust, keyed by auth user id
+CREATE TABLE verified_reporters (
+    user_id TEXT PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create trigger for verified_reporters
+CREATE TRIGGER set_verified_reporters_created_at
+BEFORE INSERT ON verified_reporters
+FOR EACH ROW
+EXECUTE FUNCTION set_created_at();
+
+-- What a moderation rule does to the complaints it matches
+CREATE TYPE moderation_action AS ENUM (
+    'publish',
+    'hold',
+    'reject'
+);
+
+-- Rules evaluated against every submitted complaint. A rule matches when all
+-- of its conditions hold; see RuleCondition for the JSON format. When several
+-- rules match, the most restrictive action wins.
+CREATE TABLE moderation_rules (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    conditions JSONB NOT NULL DEFAULT '[]',
+    action moderation_action NOT NULL,
+    -- Told to the reporter when the rule rejects a complaint
+    reason TEXT,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create trigger for moderation_rules
+CREATE TRIGGER set_moderation_rules_created_at
+BEFORE INSERT ON moderation_rules
+FOR EACH ROW
+EXECUTE FUNCTION set_created_at();
+
+-- Who submitted each complaint, when signed in, and the rules that fired on it
+ALTER TABLE complaints
+    ADD COLUMN reporter_id TEXT,
+    ADD COLUMN fired_rules TEXT[] NOT NULL DEFAULT '{}';
+
+INSERT INTO moderation_rules (name, conditions, action, reason) VALUES
+    ('short_description',
+     '[{"type": "description_shorter_than", "length": 20}]',
+     'reject',
+     'Descriptions must be at least 20 characters long'),
+    ('hold_pii', '[{"type": "contains_pii"}]', 'hold', NULL),
+    ('hold_profanity', '[{"type": "profanity", "min_severity": "mild"}]', 'hold', NULL),
+    ('publish_verified_low_risk',
+     '[{"type": "reporter_verified"},
+       {"type": "category_in", "categories": ["overcharging", "route_deviation", "other"]},
+       {"type": "not", "condition": {"type": "possible_duplicate"}}]',
+     'publish',
+     NULL);
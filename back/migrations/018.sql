@@ -0,0 +1,7 @@
+-- Count how often an image was handed to the pipeline, so images that keep
+-- crashing it end up failed instead of being retried forever
+ALTER TABLE driver_images
+    ADD COLUMN processing_attempts INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE complaint_images
+    ADD COLUMN processing_attempts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- The moderation queue looks for duplicates among exact copies of an image
+-- and among recent images, instead of comparing against every image ever
+-- uploaded
+CREATE INDEX idx_driver_images_perceptual_hash ON driver_images(perceptual_hash);
+CREATE INDEX idx_complaint_images_perceptual_hash ON complaint_images(perceptual_hash);
+CREATE INDEX idx_driver_images_hashed_created_at ON driver_images(created_at)
+    WHERE perceptual_hash IS NOT NULL;
+CREATE INDEX idx_complaint_images_hashed_created_at ON complaint_images(created_at)
+    WHERE perceptual_hash IS NOT NULL;
@@ -0,0 +1,7 @@
+-- When the window of each rate limit counter ends, so finished windows can be
+-- pruned. Windows were all one hour long so far.
+ALTER TABLE rate_limit_counters ADD COLUMN resets_at TIMESTAMP WITH TIME ZONE;
+UPDATE rate_limit_counters SET resets_at = window_start + INTERVAL '1 hour';
+ALTER TABLE rate_limit_counters ALTER COLUMN resets_at SET NOT NULL;
+
+CREATE INDEX idx_rate_limit_counters_resets_at ON rate_limit_counters(resets_at);
@@ -0,0 +1,8 @@
+-- Idempotency keys are chosen by clients, so they are scoped to whoever sent
+-- them: two submitters using the same key never see each other's responses.
+-- Keys claimed before this migration are kept under an empty submitter, which
+-- no request has, so they simply expire.
+ALTER TABLE idempotency_keys ADD COLUMN submitter VARCHAR(255) NOT NULL DEFAULT '';
+ALTER TABLE idempotency_keys ALTER COLUMN submitter DROP DEFAULT;
+ALTER TABLE idempotency_keys DROP CONSTRAINT idempotency_keys_pkey;
+ALTER TABLE idempotency_keys ADD PRIMARY KEY (submitter, idempotency_key);
@@ -0,0 +1,15 @@
+-- The complaint each driver photo was submitted with, so the photo is only
+-- shown publicly once that complaint is published
+ALTER TABLE driver_images ADD COLUMN complaint_id INTEGER REFERENCES complaints(id);
+
+-- Photos and their complaint are written in one transaction, so they share
+-- created_at. Older photos were written just before their complaint.
+UPDATE driver_images di SET complaint_id = (
+    SELECT c.id FROM complaints c
+    WHERE c.driver_id = di.driver_id
+    AND c.created_at BETWEEN di.created_at AND di.created_at + INTERVAL '1 minute'
+    ORDER BY c.created_at, c.id
+    LIMIT 1
+);
+
+CREATE INDEX idx_driver_images_complaint_id ON driver_images(complaint_id);
@@ -0,0 +1,7 @@
+-- Digest of who submitted each complaint, the signed-in reporter or else the
+-- client IP, so a resubmission can be told apart from another victim's
+-- complaint. Complaints of signed-in reporters get theirs retroactively.
+ALTER TABLE complaints ADD COLUMN submitter_hash CHAR(64);
+UPDATE complaints
+SET submitter_hash = encode(sha256(convert_to('reporter:' || reporter_id, 'UTF8')), 'hex')
+WHERE reporter_id IS NOT NULL;
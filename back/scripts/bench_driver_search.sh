@@ -0,0 +1,49 @@
+#!/usr/bin/env bash
+# Times the driver search endpoints that load images and complaints for a
+# whole page of drivers. Prints the median response time of each endpoint.
+#
+# Usage: scripts/bench_driver_search.sh [API_URL]
+#
+# API_URL defaults to http://localhost:4200/api. Set REQUESTS to change how
+# many timed requests each endpoint gets (default 30). Start the API on a
+# database migrated from scratch and loaded with bench_driver_search.sql.
+#
+# To compare two builds, such as the per-driver fan-out (the parent of
+# "[user-030] Batch image and complaint loading in driver searches") and the
+# batched queries (that commit), build each in release mode. Then run each
+# one in turn on the same database and run this script against it. Results
+# so far are in README.md.
+set -euo pipefail
+
+API_URL="${1:-http://localhost:4200/api}"
+REQUESTS="${REQUESTS:-30}"
+WARMUP=5
+
+SEARCH="query=Bench&page=1&per_page=50"
+ENDPOINTS=(
+    "with-images|/taxi/drivers/search/with-images?${SEARCH}"
+    "with-details|/taxi/drivers/search/with-details?${SEARCH}&complaints_page=1&complaints_per_page=10"
+)
+
+median_ms() {
+    sort -n | awk '{ times[NR] = $1 }
+        END {
+            middle = int((NR + 1) / 2)
+            median = (NR % 2) ? times[middle] : (times[middle] + times[middle + 1]) / 2
+            printf "%.1f", median * 1000
+        }'
+}
+
+for endpoint in "${ENDPOINTS[@]}"; do
+    name="${endpoint%%|*}"
+    url="${API_URL}${endpoint#*|}"
+
+    for _ in $(seq "$WARMUP"); do
+        curl -sf -o /dev/null "$url"
+    done
+    median=$(for _ in $(seq "$REQUESTS"); do
+        curl -sf -o /dev/null -w '%{time_total}\n' "$url"
+    done | median_ms)
+
+    printf '%-14s %8s ms  (median of %s requests)\n' "$name" "$median" "$REQUESTS"
+done
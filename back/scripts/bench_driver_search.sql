@@ -0,0 +1,41 @@
+-- Fixture for scripts/bench_driver_search.sh: 2000 drivers, each with 20
+-- published complaints and 3 photos. Load it into a freshly migrated database.
+INSERT INTO drivers (name, license_plate)
+SELECT format('Bench Driver %s', n), format('BEN-%s', lpad(n::text, 4, '0'))
+FROM generate_series(1, 2000) n;
+
+INSERT INTO complaints (driver_id, location_id, taxi_application, description, published)
+SELECT d.id, (SELECT MIN(id) FROM locations), 'uber',
+    format('Complaint %s against %s', n, d.name), true
+FROM drivers d, generate_series(1, 20) n
+WHERE d.name LIKE 'Bench Driver %';
+
+-- Photos were stored as URLs before migration 006 and as object keys after it.
+-- The fixture loads on both schemas, so older builds can be compared.
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'driver_images' AND column_name = 'image_key'
+    ) THEN
+        INSERT INTO driver_images (driver_id, image_key)
+        SELECT d.id, format('driver_images/%s/%s', d.id, n)
+        FROM drivers d, generate_series(1, 3) n
+        WHERE d.name LIKE 'Bench Driver %';
+    ELSE
+        INSERT INTO driver_images (driver_id, image_url)
+        SELECT d.id, format('https://bench.s3.amazonaws.com/driver_images/%s/%s', d.id, n)
+        FROM drivers d, generate_series(1, 3) n
+        WHERE d.name LIKE 'Bench Driver %';
+    END IF;
+
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'driver_images' AND column_name = 'processing_status'
+    ) THEN
+        UPDATE driver_images SET processing_status = 'ready'
+        WHERE driver_id IN (SELECT id FROM drivers WHERE name LIKE 'Bench Driver %');
+    END IF;
+END $$;
+
+ANALYZE;
@@ -1,10 +1,13 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::header, HttpResponse, ResponseError};
 use serde_json::Error as SerdeError;
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 
 use crate::utils::lucia;
 
+/// Names the existing complaint a rejected submission duplicates
+const DUPLICATE_OF_HEADER: &str = "Duplicate-Of";
+
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("Failed to parse response: {0}")]
@@ -31,6 +34,14 @@ pub enum ApiError {
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    /// Seconds until the client may try again
+    #[error("Too many requests, retry in {0} seconds")]
+    TooManyRequests(u64),
+
+    /// Id of the earlier complaint a submission repeats
+    #[error("Conflict: complaint {0} was already submitted with the same description")]
+    DuplicateComplaint(i32),
+
     #[error("Service unavailable: {0}")]
     ServiceUnavailable(String),
 
@@ -53,6 +64,12 @@ impl ResponseError for ApiError {
             ApiError::Forbidden(_) => HttpResponse::Forbidden().json(self.to_string()),
             ApiError::Unauthorized(_) => HttpResponse::Unauthorized().json(self.to_string()),
             ApiError::Conflict(_) => HttpResponse::Conflict().json(self.to_string()),
+            ApiError::TooManyRequests(retry_after) => HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(self.to_string()),
+            ApiError::DuplicateComplaint(complaint_id) => HttpResponse::Conflict()
+                .insert_header((DUPLICATE_OF_HEADER, complaint_id.to_string()))
+                .json(self.to_string()),
             ApiError::ServiceUnavailable(_) => {
                 HttpResponse::ServiceUnavailable().json(self.to_string())
             }
@@ -1,11 +1,25 @@
 #![allow(dead_code)]
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
-use modules::{api::config, Service};
-use utils::s3;
+use modules::{
+    api::{
+        config, run_counter_pruning, RateLimitPolicy, RateLimitedEndpoint, RateLimiter,
+        TrustedProxies,
+    },
+    port::{BucketPort, CaptchaPort, RateLimitStore},
+    CaptchaEndpoint, Service,
+};
+use utils::{
+    captcha::{SiteverifyClient, StubCaptcha, HCAPTCHA_VERIFY_URL, TURNSTILE_VERIFY_URL},
+    local_storage, lucia,
+    profanity::{ProfanityFilter, WordLists},
+    rate_limit::InMemoryRateLimitStore,
+    s3, CaptchaProvider, Config, RateLimitBackend, StorageBackend,
+};
 
 use crate::utils::database::PostgresRepository;
 
@@ -18,19 +32,124 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "info,debug");
     env_logger::init();
 
+    let settings = Config::from_env();
+    let storage_backend = settings.storage_backend;
     let repo = Arc::new(PostgresRepository::new().await);
-    let bukcet_service = Arc::new(s3::S3Repository::new().await.unwrap());
-    let service = Arc::new(Service::new(repo, bukcet_service));
+
+    // Local storage also serves its files, so keep it around for the routes below
+    let local_storage = match storage_backend {
+        StorageBackend::Local => Some(web::Data::new(
+            local_storage::LocalStorageRepository::new().await.unwrap(),
+        )),
+        StorageBackend::S3 => None,
+    };
+    let bukcet_service: Arc<dyn BucketPort> = match &local_storage {
+        Some(storage) => storage.clone().into_inner(),
+        None => Arc::new(s3::S3Repository::new().await.unwrap()),
+    };
+    let auth_service = web::Data::new(lucia::Service::new(repo.clone()));
+    let rate_limit_store: Option<Arc<dyn RateLimitStore>> = match settings.rate_limit_backend {
+        RateLimitBackend::Memory => Some(Arc::new(InMemoryRateLimitStore::new())),
+        RateLimitBackend::Postgres => Some(repo.clone()),
+        RateLimitBackend::Disabled => None,
+    };
+    if let Some(store) = &rate_limit_store {
+        tokio::spawn(run_counter_pruning(
+            store.clone(),
+            Duration::from_secs(10 * 60),
+        ));
+    }
+    let rate_limiter = rate_limit_store.map(|store| {
+        let per_hour = |max_requests| RateLimitPolicy {
+            max_requests,
+            window: Duration::from_secs(60 * 60),
+        };
+        web::Data::new(
+            RateLimiter::new(store)
+                .with_policy(
+                    RateLimitedEndpoint::CreateComplaint,
+                    per_hour(settings.rate_limit_complaints_per_hour),
+                )
+                .with_policy(
+                    RateLimitedEndpoint::GenerateImageUploadUrl,
+                    per_hour(settings.rate_limit_upload_urls_per_hour),
+                ),
+        )
+    });
+    let trusted_proxies =
+        web::Data::new(TrustedProxies(settings.rate_limit_trusted_proxies.clone()));
+    let siteverify = |default_url: &str| {
+        SiteverifyClient::new(
+            settings
+                .captcha_verify_url
+                .clone()
+                .unwrap_or_else(|| default_url.to_string()),
+            settings
+                .captcha_secret
+                .clone()
+                .expect("CAPTCHA_SECRET must be set"),
+        )
+    };
+    let captcha: Option<Arc<dyn CaptchaPort>> = match settings.captcha_provider {
+        CaptchaProvider::HCaptcha => Some(Arc::new(siteverify(HCAPTCHA_VERIFY_URL))),
+        CaptchaProvider::Turnstile => Some(Arc::new(siteverify(TURNSTILE_VERIFY_URL))),
+        CaptchaProvider::Stub => Some(Arc::new(StubCaptcha::new(
+            settings.captcha_stub_token.clone(),
+        ))),
+        CaptchaProvider::Disabled => None,
+    };
+    let mut service = Service::new(repo, bukcet_service);
+    if let Some(path) = &settings.profanity_wordlist_path {
+        let word_lists = WordLists::from_file(path)
+            .unwrap_or_else(|e| panic!("PROFANITY_WORDLIST_PATH is invalid: {}", e));
+        service = service.with_profanity_filter(ProfanityFilter::new(word_lists));
+    }
+    if let Some(captcha) = captcha {
+        let endpoints = [(
+            CaptchaEndpoint::CreateComplaint,
+            settings.captcha_create_complaint,
+        )];
+        service = service.with_captcha(
+            captcha,
+            endpoints
+                .into_iter()
+                .filter_map(|(endpoint, required)| required.then_some(endpoint)),
+        );
+    }
+    let service = Arc::new(service);
+    tokio::spawn(service.clone().run_image_pipeline());
+    if settings.upload_gc_interval_secs > 0 {
+        let grace_secs = settings
+            .upload_gc_grace_hours
+            .checked_mul(60 * 60)
+            .expect("UPLOAD_GC_GRACE_HOURS is too large");
+        tokio::spawn(service.clone().run_upload_gc(
+            Duration::from_secs(settings.upload_gc_interval_secs),
+            Duration::from_secs(grace_secs),
+            settings.upload_gc_dry_run,
+        ));
+    }
 
     log::info!("Starting HTTP server on 0.0.0.0:4200...");
     HttpServer::new(move || {
         let cors = Cors::permissive();
 
-        App::new()
+        let mut app = App::new()
             .wrap(cors)
             .wrap(Logger::default())
-            .service(web::scope("/api").configure(config))
+            .app_data(trusted_proxies.clone());
+        if let Some(rate_limiter) = &rate_limiter {
+            app = app.app_data(rate_limiter.clone());
+        }
+        if let Some(storage) = &local_storage {
+            app = app
+                .app_data(storage.clone())
+                .service(web::scope("/api/storage").configure(local_storage::config));
+        }
+
+        app.service(web::scope("/api").configure(config))
             .app_data(web::Data::new(service.clone()))
+            .app_data(auth_service.clone())
     })
     .bind("0.0.0.0:4200")?
     .run()
@@ -0,0 +1,97 @@
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use std::sync::Arc;
+
+use crate::error::ApiError;
+use crate::modules::Service;
+use crate::utils::lucia;
+
+/// Cookie set by the auth frontend holding the session id
+const SESSION_COOKIE: &str = "auth_session";
+
+/// Extracts the session id from an `Authorization: Bearer` header or the session cookie
+pub(super) fn session_id(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string())
+        .or_else(|| req.cookie(SESSION_COOKIE).map(|c| c.value().to_string()))
+        .filter(|value| !value.is_empty())
+}
+
+/// User of a valid session
+async fn session_user_id(
+    session_id: Option<String>,
+    auth: Option<web::Data<lucia::Service>>,
+) -> Result<String, ApiError> {
+    let session_id =
+        session_id.ok_or_else(|| ApiError::Unauthorized("Missing session".to_string()))?;
+    let auth = auth
+        .ok_or_else(|| ApiError::UnexpectedError("Authentication is not configured".to_string()))?;
+
+    let session = auth
+        .get_session(&session_id)
+        .await
+        .map_err(|err| match err {
+            lucia::Error::UserSessionNotFound
+            | lucia::Error::InvalidSessionId
+            | lucia::Error::SessionExpired => ApiError::Unauthorized(err.to_string()),
+            _ => ApiError::LuciaError(err),
+        })?;
+
+    Ok(session.user_id)
+}
+
+/// A request made with a valid session, whoever its user is. Take it as an
+/// `Option` where signing in is not required.
+pub struct Reporter {
+    pub user_id: String,
+}
+
+impl FromRequest for Reporter {
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let session_id = session_id(req);
+        let auth = req.app_data::<web::Data<lucia::Service>>().cloned();
+
+        Box::pin(async move {
+            Ok(Reporter {
+                user_id: session_user_id(session_id, auth).await?,
+            })
+        })
+    }
+}
+
+/// A request made with a valid session whose user is a moderator
+pub struct Moderator {
+    pub user_id: String,
+}
+
+impl FromRequest for Moderator {
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let session_id = session_id(req);
+        let auth = req.app_data::<web::Data<lucia::Service>>().cloned();
+        let service = req.app_data::<web::Data<Arc<Service>>>().cloned();
+
+        Box::pin(async move {
+            let user_id = session_user_id(session_id, auth).await?;
+            let service = service.ok_or_else(|| {
+                ApiError::UnexpectedError("Authentication is not configured".to_string())
+            })?;
+
+            if !service.is_moderator(&user_id).await? {
+                return Err(ApiError::Forbidden(
+                    "Only moderators can access this resource".to_string(),
+                ));
+            }
+
+            Ok(Moderator { user_id })
+        })
+    }
+}
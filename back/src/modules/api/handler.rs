@@ -1,10 +1,26 @@
-use actix_web::{web, HttpResponse};
+use actix_multipart::{Field, Multipart};
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ContentType, ETag, EntityTag, IfNoneMatch,
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use futures::TryStreamExt;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::error::ApiError;
-use crate::modules::{NewComplaint, Service};
-use crate::utils::database::Pagination;
+use crate::modules::{
+    CaptchaEndpoint, Complaint, ComplaintCategory, ComplaintFilters, ComplaintSortField,
+    DriverSortField, IdempotencyKey, Idempotent, NewComplaint, PlateMatchMode, RedactionRegion,
+    Service, UploadPurpose, DUPLICATE_IMAGE_MAX_DISTANCE, MAX_COMPLAINT_IMAGES,
+};
+use crate::utils::database::{CursorPagination, Pagination, Sort};
+use crate::utils::DEFAULT_UPLOAD_GC_GRACE_HOURS;
+
+use super::auth::{Moderator, Reporter};
+use super::rate_limit::client_ip;
 
 #[derive(Deserialize)]
 pub struct CreateComplaintRequest {
@@ -13,12 +29,90 @@ pub struct CreateComplaintRequest {
     pub taxi_license_plate: String,
     pub location_id: i32,
     pub taxi_application: String,
+    #[serde(default)]
+    pub category: ComplaintCategory,
+    pub incident_at: Option<chrono::DateTime<chrono::Utc>>,
     pub driver_image: Option<String>,
     pub complaint_images: Option<Vec<String>>,
 }
 
+/// Header making a complaint submission safe to retry
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses replayed for an idempotency key
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+/// Carries the token solved by the client on endpoints requiring a CAPTCHA
+const CAPTCHA_TOKEN_HEADER: &str = "X-Captcha-Token";
+
+/// The `Idempotency-Key` header of a request, if it has one
+fn idempotency_key_header(req: &HttpRequest) -> Result<Option<&str>, ApiError> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH)
+        .map(Some)
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "{} must be between 1 and {} visible ASCII characters",
+                IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH
+            ))
+        })
+}
+
+/// Who sent a request, which its idempotency key and duplicate checks are
+/// scoped to: the reporter when signed in, the client IP otherwise
+fn submitter(req: &HttpRequest, reporter: Option<&Reporter>) -> String {
+    match reporter {
+        Some(reporter) => format!("reporter:{}", reporter.user_id),
+        None => format!(
+            "ip:{}",
+            client_ip(req).map(|ip| ip.to_string()).unwrap_or_default()
+        ),
+    }
+}
+
+/// The `Idempotency-Key` of a request by `submitter`, if it has one.
+/// `request_hash` is only computed when it does.
+fn idempotency_key(
+    req: &HttpRequest,
+    submitter: &str,
+    request_hash: impl FnOnce() -> Result<String, ApiError>,
+) -> Result<Option<IdempotencyKey>, ApiError> {
+    let Some(key) = idempotency_key_header(req)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(IdempotencyKey {
+        submitter: submitter.to_string(),
+        key: key.to_string(),
+        request_hash: request_hash()?,
+    }))
+}
+
+fn captcha_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(CAPTCHA_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+}
+
+fn created_complaint_response(complaint: Idempotent<Complaint>) -> HttpResponse {
+    match complaint {
+        Idempotent::Fresh(complaint) => HttpResponse::Ok().json(complaint),
+        Idempotent::Replayed(complaint) => HttpResponse::Ok()
+            .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+            .json(complaint),
+    }
+}
+
 pub async fn create_complaint(
+    http_req: HttpRequest,
     service: web::Data<Arc<Service>>,
+    reporter: Option<Reporter>,
     req: web::Json<CreateComplaintRequest>,
 ) -> Result<HttpResponse, ApiError> {
     let new_complaint = NewComplaint {
@@ -27,12 +121,277 @@ pub async fn create_complaint(
         taxi_license_plate: req.taxi_license_plate.clone(),
         location_id: req.location_id,
         taxi_application: req.taxi_application.clone(),
+        category: req.category,
+        incident_at: req.incident_at,
         driver_image: req.driver_image.clone(),
         complaint_images: req.complaint_images.clone(),
+        reporter_id: reporter.as_ref().map(|reporter| reporter.user_id.clone()),
+        submitter: submitter(&http_req, reporter.as_ref()),
+    };
+    let idempotency_key = idempotency_key(&http_req, &new_complaint.submitter, || {
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(
+            &new_complaint,
+        )?)))
+    })?;
+    let captcha = service
+        .verify_captcha(
+            CaptchaEndpoint::CreateComplaint,
+            captcha_token(&http_req),
+            idempotency_key.as_ref(),
+        )
+        .await?;
+
+    let created_complaint = service
+        .create_complaint(new_complaint, idempotency_key.as_ref(), captcha)
+        .await?;
+    Ok(created_complaint_response(created_complaint))
+}
+
+/// Text fields of a multipart complaint, the fields of `CreateComplaintRequest`
+/// other than the images
+const FORM_TEXT_FIELDS: [&str; 7] = [
+    "description",
+    "taxi_driver_name",
+    "taxi_license_plate",
+    "location_id",
+    "taxi_application",
+    "category",
+    "incident_at",
+];
+/// Longest accepted text field of a multipart complaint
+const MAX_FORM_FIELD_BYTES: usize = 16 * 1024;
+/// Largest accepted multipart complaint, all fields and files together
+const MAX_FORM_BYTES: usize = 32 * 1024 * 1024;
+
+/// `create_complaint` for clients that cannot upload to the bucket themselves.
+/// The fields of `CreateComplaintRequest` are sent as form fields and the
+/// photos as `driver_image` and `complaint_images` files, each stored as soon
+/// as it has been read, within the size limit of its purpose. Files are held
+/// in memory one at a time while they are read, never the whole form.
+///
+/// Text fields must come before the files, so an invalid complaint is refused
+/// before anything is stored.
+///
+/// Retries send the same files under new keys, so the idempotency hash covers
+/// the field contents instead. Files of a replayed request are stored anyway and
+/// left for the upload collector.
+pub async fn create_complaint_multipart(
+    http_req: HttpRequest,
+    service: web::Data<Arc<Service>>,
+    reporter: Option<Reporter>,
+    mut form: Multipart,
+) -> Result<HttpResponse, ApiError> {
+    // Both checked before reading the body, so bots cannot fill the bucket. The
+    // request hash is not known yet, so retries need a new CAPTCHA token too.
+    idempotency_key_header(&http_req)?;
+    let captcha = service
+        .verify_captcha(
+            CaptchaEndpoint::CreateComplaint,
+            captcha_token(&http_req),
+            None,
+        )
+        .await?;
+
+    let submitter = submitter(&http_req, reporter.as_ref());
+    let mut request_hash = Sha256::new();
+    let mut remaining_bytes = MAX_FORM_BYTES;
+    let mut fields = HashMap::new();
+    let mut new_complaint = None;
+    let mut driver_image = None;
+    let mut complaint_images = Vec::new();
+    while let Some(mut field) = form
+        .try_next()
+        .await
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let purpose = match name.as_str() {
+            "driver_image" if driver_image.is_some() => {
+                return Err(ApiError::BadRequest(
+                    "Only one driver image can be uploaded".to_string(),
+                ))
+            }
+            "driver_image" => UploadPurpose::DriverPhoto,
+            "complaint_images" if complaint_images.len() == MAX_COMPLAINT_IMAGES => {
+                return Err(ApiError::BadRequest(format!(
+                    "A complaint can have at most {} images",
+                    MAX_COMPLAINT_IMAGES
+                )))
+            }
+            "complaint_images" => UploadPurpose::ComplaintEvidence,
+            text if FORM_TEXT_FIELDS.contains(&text) => {
+                if new_complaint.is_some() {
+                    return Err(ApiError::BadRequest(format!(
+                        "Form field '{}' must come before the files",
+                        name
+                    )));
+                }
+                if fields.contains_key(&name) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Form field '{}' is repeated",
+                        name
+                    )));
+                }
+                let limit = MAX_FORM_FIELD_BYTES.min(remaining_bytes);
+                let value = read_form_field(&mut field, &name, limit).await?;
+                remaining_bytes -= value.len();
+                hash_form_field(&mut request_hash, &name, &value);
+                let value = String::from_utf8(value)
+                    .map_err(|_| ApiError::BadRequest(format!("Field '{}' is not UTF-8", name)))?;
+                fields.insert(name, value);
+                continue;
+            }
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown form field '{}'",
+                    name
+                )))
+            }
+        };
+
+        // The text fields are all in by the first file
+        if new_complaint.is_none() {
+            new_complaint = Some(complaint_form(
+                std::mem::take(&mut fields),
+                reporter.as_ref(),
+                &submitter,
+            )?);
+        }
+        let limit = (purpose.max_size_bytes() as usize).min(remaining_bytes);
+        let bytes = read_form_field(&mut field, &name, limit).await?;
+        remaining_bytes -= bytes.len();
+        hash_form_field(&mut request_hash, &name, &bytes);
+        let key = service.upload_image(purpose, bytes).await?;
+        match purpose {
+            UploadPurpose::DriverPhoto => driver_image = Some(key),
+            _ => complaint_images.push(key),
+        }
+    }
+
+    let mut new_complaint = match new_complaint {
+        Some(new_complaint) => new_complaint,
+        None => complaint_form(fields, reporter.as_ref(), &submitter)?,
     };
+    new_complaint.driver_image = driver_image;
+    new_complaint.complaint_images = Some(complaint_images);
+    let idempotency_key = idempotency_key(&http_req, &new_complaint.submitter, || {
+        Ok(hex::encode(request_hash.finalize()))
+    })?;
 
-    let created_complaint = service.create_complaint(new_complaint).await?;
-    Ok(HttpResponse::Ok().json(created_complaint))
+    let created_complaint = service
+        .create_complaint(new_complaint, idempotency_key.as_ref(), captcha)
+        .await?;
+    Ok(created_complaint_response(created_complaint))
+}
+
+/// The complaint described by the text fields of a multipart form, without
+/// its images
+fn complaint_form(
+    mut fields: HashMap<String, String>,
+    reporter: Option<&Reporter>,
+    submitter: &str,
+) -> Result<NewComplaint, ApiError> {
+    let mut required = |name: &str| {
+        fields
+            .remove(name)
+            .ok_or_else(|| ApiError::BadRequest(format!("Missing form field '{}'", name)))
+    };
+
+    Ok(NewComplaint {
+        description: required("description")?,
+        taxi_driver_name: required("taxi_driver_name")?,
+        taxi_license_plate: required("taxi_license_plate")?,
+        location_id: required("location_id")?
+            .parse()
+            .map_err(|_| ApiError::BadRequest("Invalid location_id".to_string()))?,
+        taxi_application: required("taxi_application")?,
+        category: fields
+            .remove("category")
+            .map(|category| serde_json::from_value(serde_json::Value::String(category)))
+            .transpose()
+            .map_err(|e| ApiError::BadRequest(format!("Invalid category: {}", e)))?
+            .unwrap_or_default(),
+        incident_at: fields
+            .remove("incident_at")
+            .map(|incident_at| incident_at.parse())
+            .transpose()
+            .map_err(|e| ApiError::BadRequest(format!("Invalid incident_at: {}", e)))?,
+        driver_image: None,
+        complaint_images: None,
+        reporter_id: reporter.map(|reporter| reporter.user_id.clone()),
+        submitter: submitter.to_string(),
+    })
+}
+
+/// Adds a form field to a request hash, length-prefixed so fields cannot run
+/// into each other
+fn hash_form_field(hasher: &mut Sha256, name: &str, value: &[u8]) {
+    for part in [name.as_bytes(), value] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+}
+
+/// Reads a whole form field, failing as soon as it grows past `limit` bytes
+async fn read_form_field(field: &mut Field, name: &str, limit: usize) -> Result<Vec<u8>, ApiError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = field
+        .try_next()
+        .await
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?
+    {
+        if bytes.len() + chunk.len() > limit {
+            return Err(ApiError::BadRequest(format!(
+                "Field '{}' exceeds the maximum size of {} bytes",
+                name, limit
+            )));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+/// Seconds shared caches may serve a feed page before revalidating it
+const FEED_MAX_AGE_SECS: u32 = 30;
+
+pub async fn get_complaint_feed(
+    req: HttpRequest,
+    service: web::Data<Arc<Service>>,
+    web::Query(pagination): web::Query<CursorPagination>,
+    web::Query(filters): web::Query<ComplaintFilters>,
+) -> Result<HttpResponse, ApiError> {
+    let feed = service.get_complaint_feed(&filters, &pagination).await?;
+    let body = serde_json::to_vec(&feed)?;
+
+    // A digest of the body, so every instance of the API agrees on the tag
+    let etag = EntityTag::new_weak(hex::encode(&Sha256::digest(&body)[..16]));
+    let cache_control = CacheControl(vec![
+        CacheDirective::Public,
+        CacheDirective::MaxAge(FEED_MAX_AGE_SECS),
+        CacheDirective::Extension(
+            "stale-while-revalidate".to_string(),
+            Some((FEED_MAX_AGE_SECS * 4).to_string()),
+        ),
+    ]);
+
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(cache_control)
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(etag))
+        .insert_header(cache_control)
+        .content_type(ContentType::json())
+        .body(body))
 }
 
 pub async fn get_driver(
@@ -47,20 +406,37 @@ pub async fn get_driver_complaints(
     service: web::Data<Arc<Service>>,
     driver_id: web::Path<i32>,
     web::Query(pagination): web::Query<Pagination>,
+    web::Query(filters): web::Query<ComplaintFilters>,
+    web::Query(sort): web::Query<Sort<ComplaintSortField>>,
+) -> Result<HttpResponse, ApiError> {
+    let complaints = service
+        .get_driver_complaints(*driver_id, &filters, &sort, &pagination)
+        .await?;
+    Ok(HttpResponse::Ok().json(complaints))
+}
+
+pub async fn get_driver_complaints_feed(
+    service: web::Data<Arc<Service>>,
+    driver_id: web::Path<i32>,
+    web::Query(pagination): web::Query<CursorPagination>,
+    web::Query(filters): web::Query<ComplaintFilters>,
 ) -> Result<HttpResponse, ApiError> {
     let complaints = service
-        .get_driver_complaints(*driver_id, &pagination)
+        .get_driver_complaints_by_cursor(*driver_id, &filters, &pagination)
         .await?;
     Ok(HttpResponse::Ok().json(complaints))
 }
 
+/// Driver photos are only signed once a complaint they came with is published,
+/// unless a moderator is asking
 pub async fn get_driver_with_details(
     service: web::Data<Arc<Service>>,
     driver_id: web::Path<i32>,
     web::Query(pagination): web::Query<Pagination>,
+    moderator: Option<Moderator>,
 ) -> Result<HttpResponse, ApiError> {
     let driver_details = service
-        .get_driver_with_details(*driver_id, &pagination)
+        .get_driver_with_details(*driver_id, &pagination, moderator.is_some())
         .await?;
     Ok(HttpResponse::Ok().json(driver_details))
 }
@@ -68,6 +444,9 @@ pub async fn get_driver_with_details(
 #[derive(Deserialize)]
 pub struct SearchDriversQuery {
     pub query: String,
+    /// How the query is matched against plates
+    #[serde(default)]
+    pub mode: PlateMatchMode,
     pub page: u32,
     pub per_page: u32,
 }
@@ -75,56 +454,99 @@ pub struct SearchDriversQuery {
 pub async fn search_drivers(
     service: web::Data<Arc<Service>>,
     web::Query(query): web::Query<SearchDriversQuery>,
+    web::Query(filters): web::Query<ComplaintFilters>,
+    web::Query(sort): web::Query<Sort<DriverSortField>>,
 ) -> Result<HttpResponse, ApiError> {
     let pagination = Pagination {
         page: query.page,
         per_page: query.per_page,
     };
-    let drivers = service.search_drivers(&query.query, &pagination).await?;
+    let drivers = service
+        .search_drivers(&query.query, query.mode, &filters, &sort, &pagination)
+        .await?;
     Ok(HttpResponse::Ok().json(drivers))
 }
 
 pub async fn search_drivers_with_images(
     service: web::Data<Arc<Service>>,
     web::Query(query): web::Query<SearchDriversQuery>,
+    web::Query(filters): web::Query<ComplaintFilters>,
+    web::Query(sort): web::Query<Sort<DriverSortField>>,
+    moderator: Option<Moderator>,
+) -> Result<HttpResponse, ApiError> {
+    let pagination = Pagination {
+        page: query.page,
+        per_page: query.per_page,
+    };
+    let drivers = service
+        .search_drivers_with_images(
+            &query.query,
+            query.mode,
+            &filters,
+            &sort,
+            &pagination,
+            moderator.is_some(),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(drivers))
+}
+
+#[derive(Deserialize)]
+pub struct SearchDriversByPlateQuery {
+    pub plate: String,
+    #[serde(default)]
+    pub mode: PlateMatchMode,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+pub async fn search_drivers_by_license_plate(
+    service: web::Data<Arc<Service>>,
+    web::Query(query): web::Query<SearchDriversByPlateQuery>,
 ) -> Result<HttpResponse, ApiError> {
     let pagination = Pagination {
         page: query.page,
         per_page: query.per_page,
     };
     let drivers = service
-        .search_drivers_with_images(&query.query, &pagination)
+        .search_drivers_by_license_plate(&query.plate, query.mode, &pagination)
         .await?;
     Ok(HttpResponse::Ok().json(drivers))
 }
 
 #[derive(Deserialize)]
 pub struct GenerateImageUploadUrlRequest {
-    pub prefix: String,
+    pub purpose: UploadPurpose,
+    pub content_type: String,
 }
 
 pub async fn generate_image_upload_url(
     service: web::Data<Arc<Service>>,
     req: web::Json<GenerateImageUploadUrlRequest>,
 ) -> Result<HttpResponse, ApiError> {
-    let (upload_url, public_url) = service.generate_image_upload_url(&req.prefix).await?;
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "upload_url": upload_url,
-        "public_url": public_url
-    })))
+    let upload = service
+        .generate_image_upload_url(req.purpose, &req.content_type)
+        .await?;
+    Ok(HttpResponse::Ok().json(upload))
 }
 
+/// Anyone can read a complaint, but moderators also get the images of pending ones
 pub async fn get_complaint_with_images(
     service: web::Data<Arc<Service>>,
     complaint_id: web::Path<i32>,
+    moderator: Option<Moderator>,
 ) -> Result<HttpResponse, ApiError> {
-    let complaint_with_images = service.get_complaint_with_images(*complaint_id).await?;
+    let complaint_with_images = service
+        .get_complaint_with_images(*complaint_id, moderator.is_some())
+        .await?;
     Ok(HttpResponse::Ok().json(complaint_with_images))
 }
 
 #[derive(Deserialize)]
 pub struct SearchDriversWithDetailsQuery {
     pub query: String,
+    #[serde(default)]
+    pub mode: PlateMatchMode,
     pub page: u32,
     pub per_page: u32,
     pub complaints_page: u32,
@@ -134,6 +556,9 @@ pub struct SearchDriversWithDetailsQuery {
 pub async fn search_drivers_with_details(
     service: web::Data<Arc<Service>>,
     web::Query(query): web::Query<SearchDriversWithDetailsQuery>,
+    web::Query(filters): web::Query<ComplaintFilters>,
+    web::Query(sort): web::Query<Sort<DriverSortField>>,
+    moderator: Option<Moderator>,
 ) -> Result<HttpResponse, ApiError> {
     let pagination = Pagination {
         page: query.page,
@@ -144,7 +569,95 @@ pub async fn search_drivers_with_details(
         per_page: query.complaints_per_page,
     };
     let drivers = service
-        .search_drivers_with_details(&query.query, &pagination, &complaints_pagination)
+        .search_drivers_with_details(
+            &query.query,
+            query.mode,
+            &filters,
+            &sort,
+            &pagination,
+            &complaints_pagination,
+            moderator.is_some(),
+        )
         .await?;
     Ok(HttpResponse::Ok().json(drivers))
 }
+
+#[derive(Deserialize)]
+pub struct RedactComplaintImageRequest {
+    pub regions: Vec<RedactionRegion>,
+}
+
+pub async fn redact_complaint_image(
+    service: web::Data<Arc<Service>>,
+    moderator: Moderator,
+    image_id: web::Path<i32>,
+    req: web::Json<RedactComplaintImageRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let image = service
+        .redact_complaint_image(*image_id, req.into_inner().regions, &moderator.user_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(image))
+}
+
+#[derive(Deserialize)]
+pub struct CollectOrphanedUploadsQuery {
+    /// Defaults to only reporting, deleting must be asked for explicitly
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+    #[serde(default = "default_grace_hours")]
+    pub grace_hours: u64,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+fn default_grace_hours() -> u64 {
+    DEFAULT_UPLOAD_GC_GRACE_HOURS
+}
+
+pub async fn collect_orphaned_uploads(
+    service: web::Data<Arc<Service>>,
+    _moderator: Moderator,
+    web::Query(query): web::Query<CollectOrphanedUploadsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let grace_secs = query
+        .grace_hours
+        .checked_mul(60 * 60)
+        .ok_or_else(|| ApiError::BadRequest("grace_hours is too large".to_string()))?;
+    let report = service
+        .collect_orphaned_uploads(Duration::from_secs(grace_secs), query.dry_run)
+        .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn get_moderation_queue(
+    service: web::Data<Arc<Service>>,
+    _moderator: Moderator,
+    web::Query(pagination): web::Query<CursorPagination>,
+) -> Result<HttpResponse, ApiError> {
+    let complaints = service.get_moderation_queue(&pagination).await?;
+    Ok(HttpResponse::Ok().json(complaints))
+}
+
+#[derive(Deserialize)]
+pub struct ComplaintsSharingImageQuery {
+    #[serde(default = "default_max_distance")]
+    pub max_distance: u32,
+}
+
+fn default_max_distance() -> u32 {
+    DUPLICATE_IMAGE_MAX_DISTANCE
+}
+
+pub async fn get_complaints_sharing_image(
+    service: web::Data<Arc<Service>>,
+    _moderator: Moderator,
+    image_id: web::Path<i32>,
+    web::Query(query): web::Query<ComplaintsSharingImageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let complaints = service
+        .get_complaints_sharing_image(*image_id, query.max_distance)
+        .await?;
+    Ok(HttpResponse::Ok().json(complaints))
+}
@@ -1,20 +1,39 @@
-use actix_web::web;
+use actix_web::{guard, http::header, web};
 use handler::{
-    create_complaint, generate_image_upload_url, get_complaint_with_images, get_driver,
-    get_driver_complaints, get_driver_with_details, search_drivers, search_drivers_with_details,
-    search_drivers_with_images,
+    collect_orphaned_uploads, create_complaint, create_complaint_multipart,
+    generate_image_upload_url, get_complaint_feed, get_complaint_with_images,
+    get_complaints_sharing_image, get_driver, get_driver_complaints, get_driver_complaints_feed,
+    get_driver_with_details, get_moderation_queue, redact_complaint_image, search_drivers,
+    search_drivers_by_license_plate, search_drivers_with_details, search_drivers_with_images,
 };
 
+mod auth;
 mod handler;
+mod rate_limit;
+use rate_limit::RateLimit;
+pub use rate_limit::{
+    run_counter_pruning, RateLimitPolicy, RateLimitedEndpoint, RateLimiter, TrustedProxies,
+};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/taxi")
-            .route(
-                "/generate-image-upload-url",
-                web::post().to(generate_image_upload_url),
+            .service(
+                web::resource("/generate-image-upload-url")
+                    .wrap(RateLimit::new(RateLimitedEndpoint::GenerateImageUploadUrl))
+                    .route(web::post().to(generate_image_upload_url)),
+            )
+            .service(
+                web::resource("/complaint")
+                    .wrap(RateLimit::new(RateLimitedEndpoint::CreateComplaint))
+                    .route(
+                        web::post()
+                            .guard(guard::fn_guard(is_multipart))
+                            .to(create_complaint_multipart),
+                    )
+                    .route(web::post().to(create_complaint)),
             )
-            .route("/complaint", web::post().to(create_complaint))
+            .route("/complaints", web::get().to(get_complaint_feed))
             .route(
                 "/complaint/{complaint_id}",
                 web::get().to(get_complaint_with_images),
@@ -24,11 +43,19 @@ pub fn config(cfg: &mut web::ServiceConfig) {
                 "/driver/{driver_id}/complaints",
                 web::get().to(get_driver_complaints),
             )
+            .route(
+                "/driver/{driver_id}/complaints/feed",
+                web::get().to(get_driver_complaints_feed),
+            )
             .route(
                 "/driver/{driver_id}/details",
                 web::get().to(get_driver_with_details),
             )
             .route("/drivers/search", web::get().to(search_drivers))
+            .route(
+                "/drivers/search/plate",
+                web::get().to(search_drivers_by_license_plate),
+            )
             .route(
                 "/drivers/search/with-images",
                 web::get().to(search_drivers_with_images),
@@ -36,6 +63,30 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route(
                 "/drivers/search/with-details",
                 web::get().to(search_drivers_with_details),
+            )
+            .route(
+                "/moderation/complaints",
+                web::get().to(get_moderation_queue),
+            )
+            .route(
+                "/moderation/complaint-images/{image_id}/redactions",
+                web::put().to(redact_complaint_image),
+            )
+            .route(
+                "/moderation/complaint-images/{image_id}/complaints",
+                web::get().to(get_complaints_sharing_image),
+            )
+            .route(
+                "/moderation/uploads/collect",
+                web::post().to(collect_orphaned_uploads),
             ),
     );
 }
+
+fn is_multipart(ctx: &guard::GuardContext) -> bool {
+    ctx.head()
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"))
+}
@@ -0,0 +1,218 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpRequest};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::ApiError;
+use crate::modules::port::RateLimitStore;
+use crate::utils::rate_limit::IpNetwork;
+
+use super::auth::session_id;
+
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Endpoints with their own rate limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitedEndpoint {
+    CreateComplaint,
+    GenerateImageUploadUrl,
+}
+
+impl RateLimitedEndpoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitedEndpoint::CreateComplaint => "create_complaint",
+            RateLimitedEndpoint::GenerateImageUploadUrl => "generate_image_upload_url",
+        }
+    }
+}
+
+/// At most `max_requests` per client in each `window`
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitPolicy {
+    pub max_requests: u32,
+    pub window: Duration,
+}
+
+/// Prunes the counters of finished windows every `interval`, forever, so the
+/// store does not keep one counter per client it ever saw
+pub async fn run_counter_pruning(store: Arc<dyn RateLimitStore>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match store.prune().await {
+            Ok(0) => {}
+            Ok(pruned) => log::info!("Pruned {} rate limit counters", pruned),
+            Err(e) => log::error!("Rate limit counter pruning failed: {}", e),
+        }
+    }
+}
+
+/// Proxies whose X-Forwarded-For header is trusted to give the client IP.
+/// Shared as app data; without it, the peer address is taken for the client.
+pub struct TrustedProxies(pub Vec<IpNetwork>);
+
+/// IP of the client, read from X-Forwarded-For only when the request comes
+/// through one of our proxies. IPv6 clients are grouped by /64, as each
+/// of them usually holds a whole one.
+pub(super) fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let trusted_proxies = req
+        .app_data::<web::Data<TrustedProxies>>()
+        .map(|proxies| proxies.0.as_slice())
+        .unwrap_or_default();
+    let peer = req.peer_addr()?.ip().to_canonical();
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(*ip));
+
+    let mut client = peer;
+    if is_trusted(&peer) {
+        // Proxies append the address they received the request from, so the
+        // client is the rightmost address that is not one of ours
+        let forwarded: Vec<IpAddr> = req
+            .headers()
+            .get_all(FORWARDED_FOR_HEADER)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_canonical())
+            .collect();
+        if let Some(ip) = forwarded.into_iter().rev().find(|ip| !is_trusted(ip)) {
+            client = ip;
+        }
+    }
+
+    Some(match client {
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !(u64::MAX as u128))),
+        ip => ip,
+    })
+}
+
+/// Counts requests per client IP, and per session when there is one, against
+/// the policy of each endpoint. Shared as app data with the `RateLimit`
+/// middleware; without it, or without a policy for an endpoint, nothing is limited.
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    policies: HashMap<RateLimitedEndpoint, RateLimitPolicy>,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            store,
+            policies: HashMap::new(),
+        }
+    }
+
+    pub fn with_policy(mut self, endpoint: RateLimitedEndpoint, policy: RateLimitPolicy) -> Self {
+        if policy.max_requests > 0 {
+            self.policies.insert(endpoint, policy);
+        }
+        self
+    }
+
+    async fn check(
+        &self,
+        req: &ServiceRequest,
+        endpoint: RateLimitedEndpoint,
+    ) -> Result<(), ApiError> {
+        let Some(policy) = self.policies.get(&endpoint) else {
+            return Ok(());
+        };
+
+        // Sessions are optional, so they only ever add a limit on top of the IP
+        let mut keys = Vec::new();
+        if let Some(ip) = client_ip(req.request()) {
+            keys.push(format!("{}:ip:{}", endpoint.as_str(), ip));
+        }
+        if let Some(session_id) = session_id(req.request()) {
+            let session_hash = hex::encode(Sha256::digest(session_id.as_bytes()));
+            keys.push(format!(
+                "{}:session:{}",
+                endpoint.as_str(),
+                &session_hash[..32]
+            ));
+        }
+
+        for key in keys {
+            // A broken store should not take the endpoints down with it
+            let hit = match self.store.hit(&key, policy.window).await {
+                Ok(hit) => hit,
+                Err(e) => {
+                    log::warn!("Failed to count rate limit hit for {}: {}", key, e);
+                    continue;
+                }
+            };
+            if hit.hits > policy.max_requests {
+                let retry_after = (hit.resets_at - chrono::Utc::now()).num_seconds().max(1);
+                return Err(ApiError::TooManyRequests(retry_after as u64));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Middleware rejecting requests over the rate limit of `endpoint` with a 429
+pub struct RateLimit {
+    endpoint: RateLimitedEndpoint,
+}
+
+impl RateLimit {
+    pub fn new(endpoint: RateLimitedEndpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            endpoint: self.endpoint,
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    endpoint: RateLimitedEndpoint,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let endpoint = self.endpoint;
+
+        Box::pin(async move {
+            if let Some(limiter) = req.app_data::<web::Data<RateLimiter>>().cloned() {
+                limiter.check(&req, endpoint).await?;
+            }
+
+            service.call(req).await
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+
+use crate::error::ApiError;
+use crate::modules::port::CaptchaPort;
+use crate::utils::captcha::{SiteverifyClient, StubCaptcha};
+
+#[async_trait]
+impl CaptchaPort for SiteverifyClient {
+    async fn verify(&self, token: &str) -> Result<bool, ApiError> {
+        SiteverifyClient::verify(self, token).await
+    }
+}
+
+#[async_trait]
+impl CaptchaPort for StubCaptcha {
+    async fn verify(&self, token: &str) -> Result<bool, ApiError> {
+        Ok(StubCaptcha::verify(self, token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn stub() -> Arc<dyn CaptchaPort> {
+        Arc::new(StubCaptcha::new("solved"))
+    }
+
+    #[tokio::test]
+    async fn stub_accepts_its_token() {
+        assert!(stub().verify("solved").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stub_rejects_other_tokens() {
+        let captcha = stub();
+        for token in ["", "Solved", "solved ", "unsolved"] {
+            assert!(!captcha.verify(token).await.unwrap(), "{:?}", token);
+        }
+    }
+}
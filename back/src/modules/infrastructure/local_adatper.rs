@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use crate::error::ApiError;
+use crate::modules::port::BucketPort;
+use crate::modules::{BucketObject, ObjectMetadata, PresignedPost, UploadPolicy};
+use crate::utils::local_storage::LocalStorageRepository;
+
+#[async_trait]
+impl BucketPort for LocalStorageRepository {
+    async fn generate_upload_url(
+        &self,
+        key: &str,
+        policy: &UploadPolicy,
+    ) -> Result<PresignedPost, ApiError> {
+        // Fail early on keys the upload endpoint would reject
+        self.object_path(key)?;
+
+        Ok(self.presigned_post(key, policy))
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, ApiError> {
+        LocalStorageRepository::get_object(self, key).await
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<(), ApiError> {
+        // Files carry no content type, it is sniffed when served
+        LocalStorageRepository::put_object(self, key, &bytes).await
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, ApiError> {
+        LocalStorageRepository::list_objects(self, prefix).await
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        self.object_url(key)
+    }
+
+    async fn signed_url(
+        &self,
+        key: &str,
+        signed_at: chrono::DateTime<chrono::Utc>,
+        expires_in: Duration,
+    ) -> Result<String, ApiError> {
+        self.object_path(key)?;
+
+        Ok(self.presigned_get(key, signed_at, expires_in))
+    }
+
+    async fn object_metadata(&self, key: &str) -> Result<ObjectMetadata, ApiError> {
+        LocalStorageRepository::object_metadata(self, key).await
+    }
+
+    async fn delete_image(&self, key: &str) -> Result<(), ApiError> {
+        self.delete_object(key).await
+    }
+}
@@ -0,0 +1,18 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use crate::error::ApiError;
+use crate::modules::port::RateLimitStore;
+use crate::modules::RateLimitHit;
+use crate::utils::rate_limit::InMemoryRateLimitStore;
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<RateLimitHit, ApiError> {
+        Ok(InMemoryRateLimitStore::hit(self, key, window))
+    }
+
+    async fn prune(&self) -> Result<u64, ApiError> {
+        Ok(InMemoryRateLimitStore::prune(self) as u64)
+    }
+}
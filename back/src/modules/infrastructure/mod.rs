@@ -1,2 +1,5 @@
+pub mod captcha_adatper;
+pub mod local_adatper;
+pub mod memory_adatper;
 pub mod pg_adatper;
 pub mod s3_adatper;
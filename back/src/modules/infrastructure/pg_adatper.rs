@@ -1,13 +1,412 @@
 use async_trait::async_trait;
+use sqlx::{postgres::PgArguments, Arguments, FromRow, PgExecutor, Postgres, Transaction};
+use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::{
     error::ApiError,
-    modules::{port::DBRepository, Complaint, ComplaintImage, Driver, DriverImage, Location},
-    utils::database::{PaginatedRecord, Pagination, PostgresRepository},
+    modules::{
+        port::{DBRepository, RateLimitStore, UnitOfWork},
+        Complaint, ComplaintFeedItem, ComplaintImage, ComplaintImageRedaction, ComplaintSortField,
+        ComplaintWithImages, Country, Driver, DriverImage, DriverSortField, DuplicateImage,
+        IdempotencyKey, IdempotencyRecord, ImageKind, ImageProcessingStatus, Location,
+        ModerationAction, ModerationRule, PendingImage, PlateMatch, PlateMatchMode,
+        ProcessedImageObjects, RateLimitHit, SharedImageComplaint, SimilarComplaint,
+    },
+    utils::{
+        database::{
+            extend_pg_arguments, to_pg_arguments, Cursor, CursorPage, CursorPagination, Filter,
+            FilterField, PaginatedRecord, Pagination, PostgresRepository, Sort, SortOrder, Value,
+        },
+        rate_limit::current_window,
+    },
 };
 
+/// Complaint fields exposed to search filters. Queries using them alias
+/// `complaints` as `c` and join `locations` as `l`.
+const COMPLAINT_FILTER_FIELDS: &[FilterField] = &[
+    FilterField::new("location_id", "c.location_id"),
+    FilterField::new("country", "l.country::text"),
+    FilterField::new("state", "l.state"),
+    FilterField::new("taxi_application", "c.taxi_application"),
+    FilterField::new("category", "c.category::text"),
+    FilterField::new("incident_at", "COALESCE(c.incident_at, c.created_at)"),
+];
+
+/// Condition matching the plates of drivers `d` against the plate bound to
+/// `placeholder`. Fuzzy matches are plates whose normalized form is similar
+/// to the query or within one edit of it.
+fn plate_condition(mode: PlateMatchMode, placeholder: usize) -> String {
+    match mode {
+        PlateMatchMode::Exact => format!(
+            "canonical_plate(d.license_plate) = canonical_plate(${})",
+            placeholder
+        ),
+        PlateMatchMode::Fuzzy => format!(
+            "(normalize_plate(d.license_plate) % normalize_plate(${0})
+            OR levenshtein(normalize_plate(d.license_plate), normalize_plate(${0})) <= 1)",
+            placeholder
+        ),
+    }
+}
+
+/// A complaint with its position and the number of complaints in its driver's partition
+#[derive(FromRow)]
+struct RankedComplaint {
+    #[sqlx(flatten)]
+    complaint: Complaint,
+    row_number: i64,
+    total_items: i64,
+}
+
+/// A moderation rule with its conditions still as JSON
+#[derive(FromRow)]
+struct ModerationRuleRow {
+    id: i32,
+    name: String,
+    conditions: String,
+    action: ModerationAction,
+    reason: Option<String>,
+}
+
+/// A feed complaint joined with its driver, location and first image
+#[derive(FromRow)]
+struct ComplaintFeedRow {
+    #[sqlx(flatten)]
+    complaint: Complaint,
+    driver_name: String,
+    driver_license_plate: String,
+    location_country: Country,
+    location_state: String,
+    image_id: Option<i32>,
+    image_key: Option<String>,
+    image_content_type: Option<String>,
+    image_size_bytes: Option<i64>,
+    image_sha256: Option<String>,
+    image_thumbnail_key: Option<String>,
+    image_width: Option<i32>,
+    image_height: Option<i32>,
+    image_redacted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<ComplaintFeedRow> for ComplaintFeedItem {
+    fn from(row: ComplaintFeedRow) -> Self {
+        let images = match (row.image_id, row.image_key) {
+            (Some(id), Some(image_key)) => vec![ComplaintImage {
+                id,
+                complaint_id: row.complaint.id,
+                image_key,
+                content_type: row.image_content_type,
+                size_bytes: row.image_size_bytes,
+                sha256: row.image_sha256,
+                thumbnail_key: row.image_thumbnail_key,
+                width: row.image_width,
+                height: row.image_height,
+                processing_status: ImageProcessingStatus::Ready,
+                image_url: None,
+                thumbnail_url: None,
+                original_key: None,
+                redacted_at: row.image_redacted_at,
+            }],
+            _ => Vec::new(),
+        };
+
+        Self {
+            driver: Driver {
+                id: row.complaint.driver_id,
+                name: row.driver_name,
+                license_plate: row.driver_license_plate,
+            },
+            location: Location {
+                id: row.complaint.location_id,
+                country: row.location_country,
+                state: row.location_state,
+            },
+            complaint: ComplaintWithImages {
+                complaint: row.complaint,
+                images,
+                original_description: None,
+            },
+        }
+    }
+}
+
+/// Processing claims older than this are assumed abandoned and handed out again
+const IMAGE_PROCESSING_TIMEOUT: &str = "10 minutes";
+/// Images whose processing was started this many times without finishing are
+/// marked failed
+const MAX_IMAGE_PROCESSING_ATTEMPTS: i32 = 3;
+
+/// How long a response is replayed for its idempotency key
+const IDEMPOTENCY_KEY_TTL: &str = "24 hours";
+
+fn image_table(kind: ImageKind) -> &'static str {
+    match kind {
+        ImageKind::Driver => "driver_images",
+        ImageKind::Complaint => "complaint_images",
+    }
+}
+
+/// Number of bits differing between two perceptual hash columns, NULL when either is
+fn hamming_distance(left: &str, right: &str) -> String {
+    format!("bit_count(({} # {})::bit(64))::int", left, right)
+}
+
+/// ORDER BY expression for a complaint listing aliased as `c`
+fn complaint_order_by(sort: &Sort<ComplaintSortField>) -> String {
+    let column = match sort.sort_by {
+        ComplaintSortField::Submitted => "c.id",
+        ComplaintSortField::IncidentDate => "COALESCE(c.incident_at, c.created_at)",
+        ComplaintSortField::Severity => "complaint_severity(c.category)",
+    };
+    format!("{} {}, c.id", column, sort.order().as_sql())
+}
+
+/// ORDER BY expression for a driver search grouped by `d.id` over complaints `c`
+fn driver_order_by(sort: &Sort<DriverSortField>) -> String {
+    let column = match sort.sort_by {
+        DriverSortField::Alphabetical => "d.name",
+        DriverSortField::MostComplaints => "COUNT(c.id)",
+        DriverSortField::MostRecentComplaint => "MAX(c.created_at)",
+        DriverSortField::HighestSeverity => "MAX(complaint_severity(c.category))",
+    };
+    format!("{} {}, d.id", column, sort.order().as_sql())
+}
+
+/// Writes shared by the repository and units of work, run on either a pool
+/// connection or the unit of work's transaction
+async fn insert_driver_image<'e>(
+    executor: impl PgExecutor<'e>,
+    driver_image: &DriverImage,
+) -> Result<DriverImage, ApiError> {
+    sqlx::query_as::<_, DriverImage>(
+        "INSERT INTO driver_images
+            (driver_id, complaint_id, image_key, content_type, size_bytes, sha256)
+        VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(driver_image.driver_id)
+    .bind(driver_image.complaint_id)
+    .bind(&driver_image.image_key)
+    .bind(&driver_image.content_type)
+    .bind(driver_image.size_bytes)
+    .bind(&driver_image.sha256)
+    .fetch_one(executor)
+    .await
+    .map_err(ApiError::DatabaseError)
+}
+
+async fn insert_complaint<'e>(
+    executor: impl PgExecutor<'e>,
+    complaint: &Complaint,
+) -> Result<Complaint, ApiError> {
+    sqlx::query_as::<_, Complaint>(
+        "INSERT INTO complaints (driver_id, location_id, taxi_application, description, category, incident_at, duplicate_of_id, original_description, pii_kinds, profanity_severity, reporter_id, fired_rules, published, submitter_hash) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) RETURNING *",
+    )
+    .bind(complaint.driver_id)
+    .bind(complaint.location_id)
+    .bind(&complaint.taxi_application)
+    .bind(&complaint.description)
+    .bind(complaint.category)
+    .bind(complaint.incident_at)
+    .bind(complaint.duplicate_of_id)
+    .bind(&complaint.original_description)
+    .bind(&complaint.pii_kinds)
+    .bind(complaint.profanity_severity)
+    .bind(&complaint.reporter_id)
+    .bind(&complaint.fired_rules)
+    .bind(complaint.published)
+    .bind(&complaint.submitter_hash)
+    .fetch_one(executor)
+    .await
+    .map_err(ApiError::DatabaseError)
+}
+
+async fn insert_complaint_image<'e>(
+    executor: impl PgExecutor<'e>,
+    complaint_image: &ComplaintImage,
+) -> Result<ComplaintImage, ApiError> {
+    sqlx::query_as::<_, ComplaintImage>(
+        "INSERT INTO complaint_images (complaint_id, image_key, content_type, size_bytes, sha256)
+        VALUES ($1, $2, $3, $4, $5) RETURNING *",
+    )
+    .bind(complaint_image.complaint_id)
+    .bind(&complaint_image.image_key)
+    .bind(&complaint_image.content_type)
+    .bind(complaint_image.size_bytes)
+    .bind(&complaint_image.sha256)
+    .fetch_one(executor)
+    .await
+    .map_err(ApiError::DatabaseError)
+}
+
+/// A Postgres transaction, rolled back by sqlx when dropped uncommitted
+pub struct PgUnitOfWork {
+    tx: Transaction<'static, Postgres>,
+}
+
+#[async_trait]
+impl UnitOfWork for PgUnitOfWork {
+    async fn upsert_driver(&mut self, driver: &Driver) -> Result<Driver, ApiError> {
+        // Updating on conflict, even to the same values, makes the statement
+        // return the existing row and lock it until the transaction ends
+        sqlx::query_as::<_, Driver>(
+            "INSERT INTO drivers (name, license_plate) VALUES ($1, $2)
+            ON CONFLICT (name, license_plate) DO UPDATE SET name = EXCLUDED.name
+            RETURNING *",
+        )
+        .bind(&driver.name)
+        .bind(&driver.license_plate)
+        .fetch_one(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+
+    async fn add_driver_image(
+        &mut self,
+        driver_image: &DriverImage,
+    ) -> Result<DriverImage, ApiError> {
+        insert_driver_image(&mut *self.tx, driver_image).await
+    }
+
+    async fn create_complaint(&mut self, complaint: &Complaint) -> Result<Complaint, ApiError> {
+        insert_complaint(&mut *self.tx, complaint).await
+    }
+
+    async fn find_similar_complaint(
+        &mut self,
+        driver_id: i32,
+        description: &str,
+        submitter_hash: &str,
+        window: Duration,
+        min_similarity: f32,
+    ) -> Result<Option<SimilarComplaint>, ApiError> {
+        sqlx::query_as::<_, SimilarComplaint>(
+            "SELECT * FROM (
+                SELECT id, category, created_at,
+                    similarity(lower(description), lower($2)) AS similarity,
+                    submitter_hash IS NOT DISTINCT FROM $5 AS same_submitter
+                FROM complaints
+                WHERE driver_id = $1
+                AND created_at > CURRENT_TIMESTAMP - make_interval(secs => $3)
+            ) recent
+            WHERE similarity >= $4
+            ORDER BY same_submitter DESC, similarity DESC, created_at DESC
+            LIMIT 1",
+        )
+        .bind(driver_id)
+        .bind(description)
+        .bind(window.as_secs_f64())
+        .bind(min_similarity)
+        .bind(submitter_hash)
+        .fetch_optional(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+
+    async fn add_complaint_image(
+        &mut self,
+        complaint_image: &ComplaintImage,
+    ) -> Result<ComplaintImage, ApiError> {
+        insert_complaint_image(&mut *self.tx, complaint_image).await
+    }
+
+    async fn claim_image_uploads(
+        &mut self,
+        image_keys: &[String],
+    ) -> Result<Vec<String>, ApiError> {
+        // The update only applies when every key is available, so a request
+        // with one bad key leaves the others free to be attached later
+        sqlx::query_scalar(
+            "WITH available AS (
+                SELECT image_key FROM image_uploads
+                WHERE image_key = ANY($1) AND attached_at IS NULL
+                FOR UPDATE
+            ), claimed AS (
+                UPDATE image_uploads SET attached_at = CURRENT_TIMESTAMP
+                WHERE image_key IN (SELECT image_key FROM available)
+                AND (SELECT COUNT(*) FROM available) = cardinality($1)
+            )
+            SELECT image_key FROM available",
+        )
+        .bind(image_keys)
+        .fetch_all(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+
+    async fn claim_idempotency_key(
+        &mut self,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<IdempotencyRecord>, ApiError> {
+        // Expired keys are taken over as if they were new
+        let claimed = sqlx::query(&format!(
+            "INSERT INTO idempotency_keys (submitter, idempotency_key, request_hash)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (submitter, idempotency_key) DO UPDATE
+            SET request_hash = EXCLUDED.request_hash, response = NULL,
+                created_at = CURRENT_TIMESTAMP
+            WHERE idempotency_keys.created_at < CURRENT_TIMESTAMP - INTERVAL '{}'",
+            IDEMPOTENCY_KEY_TTL
+        ))
+        .bind(&idempotency_key.submitter)
+        .bind(&idempotency_key.key)
+        .bind(&idempotency_key.request_hash)
+        .execute(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)?
+        .rows_affected()
+            == 1;
+        if claimed {
+            return Ok(None);
+        }
+
+        sqlx::query_as::<_, IdempotencyRecord>(
+            "SELECT request_hash, response::text AS response
+            FROM idempotency_keys WHERE submitter = $1 AND idempotency_key = $2",
+        )
+        .bind(&idempotency_key.submitter)
+        .bind(&idempotency_key.key)
+        .fetch_optional(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+
+    async fn save_idempotent_response(
+        &mut self,
+        idempotency_key: &IdempotencyKey,
+        response: &str,
+    ) -> Result<(), ApiError> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET response = $3::jsonb
+            WHERE submitter = $1 AND idempotency_key = $2",
+        )
+        .bind(&idempotency_key.submitter)
+        .bind(&idempotency_key.key)
+        .bind(response)
+        .execute(&mut *self.tx)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), ApiError> {
+        self.tx.commit().await.map_err(ApiError::DatabaseError)
+    }
+}
+
 #[async_trait]
 impl DBRepository for PostgresRepository {
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork>, ApiError> {
+        let tx = self
+            .pg_pool
+            .begin()
+            .await
+            .map_err(ApiError::DatabaseError)?;
+        Ok(Box::new(PgUnitOfWork { tx }))
+    }
+
     // Driver operations
     async fn create_driver(&self, driver: &Driver) -> Result<Driver, ApiError> {
         sqlx::query_as::<_, Driver>(
@@ -33,18 +432,30 @@ impl DBRepository for PostgresRepository {
             })
     }
 
-    async fn get_driver_by_license_plate(&self, license_plate: &str) -> Result<Driver, ApiError> {
-        sqlx::query_as::<_, Driver>("SELECT * FROM drivers WHERE license_plate = $1")
-            .bind(license_plate)
-            .fetch_one(&*self.pg_pool)
-            .await
-            .map_err(|err| match err {
-                sqlx::Error::RowNotFound => ApiError::NotFound(format!(
-                    "Driver with license plate {} not found",
-                    license_plate
-                )),
-                _ => ApiError::DatabaseError(err),
-            })
+    async fn get_driver_by_license_plate(
+        &self,
+        license_plate: &str,
+        mode: PlateMatchMode,
+    ) -> Result<Driver, ApiError> {
+        sqlx::query_as::<_, Driver>(&format!(
+            "SELECT d.* FROM drivers d
+            WHERE {}
+            ORDER BY levenshtein(normalize_plate(d.license_plate), normalize_plate($1)),
+                similarity(normalize_plate(d.license_plate), normalize_plate($1)) DESC,
+                d.id
+            LIMIT 1",
+            plate_condition(mode, 1)
+        ))
+        .bind(license_plate)
+        .fetch_one(&*self.pg_pool)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => ApiError::NotFound(format!(
+                "Driver with license plate {} not found",
+                license_plate
+            )),
+            _ => ApiError::DatabaseError(err),
+        })
     }
 
     async fn update_driver(&self, driver: &Driver) -> Result<Driver, ApiError> {
@@ -70,14 +481,7 @@ impl DBRepository for PostgresRepository {
 
     // Driver Image operations
     async fn add_driver_image(&self, driver_image: &DriverImage) -> Result<DriverImage, ApiError> {
-        sqlx::query_as::<_, DriverImage>(
-            "INSERT INTO driver_images (driver_id, image_url) VALUES ($1, $2) RETURNING *",
-        )
-        .bind(driver_image.driver_id)
-        .bind(&driver_image.image_url)
-        .fetch_one(&*self.pg_pool)
-        .await
-        .map_err(ApiError::DatabaseError)
+        insert_driver_image(&*self.pg_pool, driver_image).await
     }
 
     async fn get_driver_images(
@@ -88,9 +492,11 @@ impl DBRepository for PostgresRepository {
         let offset = (pagination.page - 1) * pagination.per_page;
 
         let images = sqlx::query_as::<_, DriverImage>(
-            "SELECT * FROM driver_images 
-            WHERE driver_id = $1
-            ORDER BY id 
+            "SELECT di.*, COALESCE(c.published, false) AS published
+            FROM driver_images di
+            LEFT JOIN complaints c ON c.id = di.complaint_id
+            WHERE di.driver_id = $1 AND di.processing_status = 'ready'
+            ORDER BY di.id
             LIMIT $2 OFFSET $3",
         )
         .bind(driver_id)
@@ -101,7 +507,9 @@ impl DBRepository for PostgresRepository {
         .map_err(ApiError::DatabaseError)?;
 
         let total_items: i64 =
-            sqlx::query_scalar("SELECT COUNT(*) FROM driver_images WHERE driver_id = $1")
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM driver_images WHERE driver_id = $1 AND processing_status = 'ready'",
+            )
                 .bind(driver_id)
                 .fetch_one(&*self.pg_pool)
                 .await
@@ -115,6 +523,40 @@ impl DBRepository for PostgresRepository {
         ))
     }
 
+    async fn get_driver_images_for_drivers(
+        &self,
+        driver_ids: &[i32],
+        per_driver: u32,
+    ) -> Result<HashMap<i32, Vec<DriverImage>>, ApiError> {
+        let images = sqlx::query_as::<_, DriverImage>(
+            "SELECT * FROM (
+                SELECT di.*, COALESCE(c.published, false) AS published,
+                    ROW_NUMBER() OVER (PARTITION BY di.driver_id ORDER BY di.id) AS row_number
+                FROM driver_images di
+                LEFT JOIN complaints c ON c.id = di.complaint_id
+                WHERE di.driver_id = ANY($1) AND di.processing_status = 'ready'
+            ) ranked
+            WHERE row_number <= $2
+            ORDER BY driver_id, id",
+        )
+        .bind(driver_ids)
+        .bind(per_driver as i64)
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        let mut images_by_driver: HashMap<i32, Vec<DriverImage>> =
+            driver_ids.iter().map(|id| (*id, Vec::new())).collect();
+        for image in images {
+            images_by_driver
+                .entry(image.driver_id)
+                .or_default()
+                .push(image);
+        }
+
+        Ok(images_by_driver)
+    }
+
     async fn delete_driver_image(&self, id: i32) -> Result<(), ApiError> {
         sqlx::query("DELETE FROM driver_images WHERE id = $1")
             .bind(id)
@@ -124,21 +566,148 @@ impl DBRepository for PostgresRepository {
         Ok(())
     }
 
-    // Complaint operations
-    async fn create_complaint(&self, complaint: &Complaint) -> Result<Complaint, ApiError> {
-        sqlx::query_as::<_, Complaint>(
-            "INSERT INTO complaints (driver_id, location_id, taxi_application, description) 
-            VALUES ($1, $2, $3, $4) RETURNING *",
+    async fn claim_pending_images(&self, limit: u32) -> Result<Vec<PendingImage>, ApiError> {
+        let mut images = Vec::new();
+        for kind in [ImageKind::Driver, ImageKind::Complaint] {
+            let remaining = limit as usize - images.len();
+            if remaining == 0 {
+                break;
+            }
+
+            sqlx::query(&format!(
+                "UPDATE {table} SET processing_status = 'failed',
+                    processing_error = 'Gave up after ' || processing_attempts || ' attempts'
+                WHERE processing_status = 'processing'
+                AND processing_started_at < CURRENT_TIMESTAMP - INTERVAL '{timeout}'
+                AND processing_attempts >= $1",
+                table = image_table(kind),
+                timeout = IMAGE_PROCESSING_TIMEOUT,
+            ))
+            .bind(MAX_IMAGE_PROCESSING_ATTEMPTS)
+            .execute(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+
+            let claimed: Vec<(i32, String)> = sqlx::query_as(&format!(
+                "UPDATE {table} SET processing_status = 'processing',
+                    processing_started_at = CURRENT_TIMESTAMP,
+                    processing_attempts = processing_attempts + 1
+                WHERE id IN (
+                    SELECT id FROM {table}
+                    WHERE processing_status = 'pending'
+                    OR (processing_status = 'processing'
+                        AND processing_started_at < CURRENT_TIMESTAMP - INTERVAL '{timeout}')
+                    ORDER BY id
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, image_key",
+                table = image_table(kind),
+                timeout = IMAGE_PROCESSING_TIMEOUT,
+            ))
+            .bind(remaining as i64)
+            .fetch_all(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+
+            images.extend(claimed.into_iter().map(|(id, image_key)| PendingImage {
+                kind,
+                id,
+                image_key,
+            }));
+        }
+
+        Ok(images)
+    }
+
+    async fn complete_image_processing(
+        &self,
+        image: &PendingImage,
+        objects: &ProcessedImageObjects,
+    ) -> Result<(), ApiError> {
+        sqlx::query(&format!(
+            "UPDATE {} SET image_key = $2, thumbnail_key = $3, width = $4, height = $5,
+                content_type = $6, size_bytes = $7, sha256 = $8, perceptual_hash = $9,
+                processing_status = 'ready', processing_error = NULL
+            WHERE id = $1",
+            image_table(image.kind)
+        ))
+        .bind(image.id)
+        .bind(&objects.image_key)
+        .bind(&objects.thumbnail_key)
+        .bind(objects.width)
+        .bind(objects.height)
+        .bind(&objects.metadata.content_type)
+        .bind(objects.metadata.size_bytes)
+        .bind(&objects.metadata.sha256)
+        .bind(objects.perceptual_hash)
+        .execute(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    async fn fail_image_processing(
+        &self,
+        image: &PendingImage,
+        error: &str,
+    ) -> Result<(), ApiError> {
+        sqlx::query(&format!(
+            "UPDATE {} SET processing_status = 'failed', processing_error = $2 WHERE id = $1",
+            image_table(image.kind)
+        ))
+        .bind(image.id)
+        .bind(error)
+        .execute(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    async fn record_image_upload(&self, image_key: &str) -> Result<(), ApiError> {
+        sqlx::query("INSERT INTO image_uploads (image_key) VALUES ($1)")
+            .bind(image_key)
+            .execute(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    async fn get_referenced_image_keys(
+        &self,
+        image_keys: &[String],
+    ) -> Result<Vec<String>, ApiError> {
+        sqlx::query_scalar(
+            "SELECT image_key FROM driver_images WHERE image_key = ANY($1)
+            UNION SELECT thumbnail_key FROM driver_images WHERE thumbnail_key = ANY($1)
+            UNION SELECT image_key FROM complaint_images WHERE image_key = ANY($1)
+            UNION SELECT thumbnail_key FROM complaint_images WHERE thumbnail_key = ANY($1)
+            UNION SELECT original_key FROM complaint_images WHERE original_key = ANY($1)",
         )
-        .bind(complaint.driver_id)
-        .bind(complaint.location_id)
-        .bind(&complaint.taxi_application)
-        .bind(&complaint.description)
-        .fetch_one(&*self.pg_pool)
+        .bind(image_keys)
+        .fetch_all(&*self.pg_pool)
         .await
         .map_err(ApiError::DatabaseError)
     }
 
+    async fn delete_unattached_image_uploads(&self, image_keys: &[String]) -> Result<(), ApiError> {
+        sqlx::query("DELETE FROM image_uploads WHERE image_key = ANY($1) AND attached_at IS NULL")
+            .bind(image_keys)
+            .execute(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Complaint operations
+    async fn create_complaint(&self, complaint: &Complaint) -> Result<Complaint, ApiError> {
+        insert_complaint(&*self.pg_pool, complaint).await
+    }
+
     async fn get_complaint_by_id(&self, id: i32) -> Result<Complaint, ApiError> {
         sqlx::query_as::<_, Complaint>("SELECT * FROM complaints WHERE id = $1")
             .bind(id)
@@ -155,13 +724,16 @@ impl DBRepository for PostgresRepository {
     async fn update_complaint(&self, complaint: &Complaint) -> Result<Complaint, ApiError> {
         sqlx::query_as::<_, Complaint>(
             "UPDATE complaints 
-            SET driver_id = $1, location_id = $2, taxi_application = $3, description = $4 
-            WHERE id = $5 RETURNING *",
+            SET driver_id = $1, location_id = $2, taxi_application = $3, description = $4, 
+                category = $5, incident_at = $6 
+            WHERE id = $7 RETURNING *",
         )
         .bind(complaint.driver_id)
         .bind(complaint.location_id)
         .bind(&complaint.taxi_application)
         .bind(&complaint.description)
+        .bind(complaint.category)
+        .bind(complaint.incident_at)
         .bind(complaint.id)
         .fetch_one(&*self.pg_pool)
         .await
@@ -180,28 +752,48 @@ impl DBRepository for PostgresRepository {
     async fn get_complaints_for_driver(
         &self,
         driver_id: i32,
+        filter: &Filter,
+        sort: &Sort<ComplaintSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Complaint>, ApiError> {
         let offset = (pagination.page - 1) * pagination.per_page;
+        let (conditions, mut args) = filter.build_for_sqlx(COMPLAINT_FILTER_FIELDS, 2)?;
+        args.insert(0, Value::from(driver_id));
 
-        let complaints = sqlx::query_as::<_, Complaint>(
-            "SELECT * FROM complaints 
-            WHERE driver_id = $1 AND published = true
-            ORDER BY id 
-            LIMIT $2 OFFSET $3",
+        let total_items: i64 = sqlx::query_scalar_with(
+            &format!(
+                "SELECT COUNT(*) FROM complaints c
+                INNER JOIN locations l ON l.id = c.location_id
+                WHERE c.driver_id = $1 AND c.published = true
+                AND {}",
+                conditions
+            ),
+            to_pg_arguments(args.clone()),
         )
-        .bind(driver_id)
-        .bind(pagination.per_page as i64)
-        .bind(offset as i64)
-        .fetch_all(&*self.pg_pool)
+        .fetch_one(&*self.pg_pool)
         .await
         .map_err(ApiError::DatabaseError)?;
 
-        let total_items: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM complaints WHERE driver_id = $1 AND published = true",
+        let limit_placeholder = args.len() + 1;
+        args.push(Value::from(pagination.per_page));
+        args.push(Value::from(offset));
+
+        let complaints = sqlx::query_as_with::<_, Complaint, _>(
+            &format!(
+                "SELECT c.* FROM complaints c
+                INNER JOIN locations l ON l.id = c.location_id
+                WHERE c.driver_id = $1 AND c.published = true
+                AND {}
+                ORDER BY {}
+                LIMIT ${} OFFSET ${}",
+                conditions,
+                complaint_order_by(sort),
+                limit_placeholder,
+                limit_placeholder + 1
+            ),
+            to_pg_arguments(args),
         )
-        .bind(driver_id)
-        .fetch_one(&*self.pg_pool)
+        .fetch_all(&*self.pg_pool)
         .await
         .map_err(ApiError::DatabaseError)?;
 
@@ -210,7 +802,228 @@ impl DBRepository for PostgresRepository {
             total_items as u64,
             pagination.page,
             pagination.per_page,
-        ))
+        )
+        .with_sort(Some(sort.applied())))
+    }
+
+    async fn get_complaints_for_drivers(
+        &self,
+        driver_ids: &[i32],
+        filter: &Filter,
+        sort: &Sort<ComplaintSortField>,
+        pagination: &Pagination,
+    ) -> Result<HashMap<i32, PaginatedRecord<Complaint>>, ApiError> {
+        let offset = (pagination.page - 1) * pagination.per_page;
+        let (conditions, mut args) = filter.build_for_sqlx(COMPLAINT_FILTER_FIELDS, 2)?;
+        let offset_placeholder = args.len() + 2;
+        args.push(Value::from(offset));
+        args.push(Value::from(offset + pagination.per_page));
+
+        let mut arguments = PgArguments::default();
+        arguments.add(driver_ids);
+        extend_pg_arguments(&mut arguments, args);
+
+        let complaints = sqlx::query_as_with::<_, RankedComplaint, _>(
+            &format!(
+                "SELECT * FROM (
+                    SELECT c.*,
+                        ROW_NUMBER() OVER (PARTITION BY c.driver_id ORDER BY {}) AS row_number,
+                        COUNT(*) OVER (PARTITION BY c.driver_id) AS total_items
+                    FROM complaints c
+                    INNER JOIN locations l ON l.id = c.location_id
+                    WHERE c.driver_id = ANY($1) AND c.published = true
+                    AND {}
+                ) ranked
+                WHERE (row_number > ${} AND row_number <= ${}) OR row_number = 1
+                ORDER BY driver_id, row_number",
+                complaint_order_by(sort),
+                conditions,
+                offset_placeholder,
+                offset_placeholder + 1
+            ),
+            arguments,
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        let mut totals: HashMap<i32, u64> = HashMap::new();
+        let mut complaints_by_driver: HashMap<i32, Vec<Complaint>> = HashMap::new();
+        for ranked in complaints {
+            // The first row of every driver is always returned so that drivers whose
+            // page is past their last complaint still report their total
+            totals.insert(ranked.complaint.driver_id, ranked.total_items as u64);
+            if ranked.row_number <= offset as i64 {
+                continue;
+            }
+            complaints_by_driver
+                .entry(ranked.complaint.driver_id)
+                .or_default()
+                .push(ranked.complaint);
+        }
+
+        Ok(driver_ids
+            .iter()
+            .map(|id| {
+                let record = PaginatedRecord::new(
+                    complaints_by_driver.remove(id).unwrap_or_default(),
+                    totals.get(id).copied().unwrap_or(0),
+                    pagination.page,
+                    pagination.per_page,
+                )
+                .with_sort(Some(sort.applied()));
+                (*id, record)
+            })
+            .collect())
+    }
+
+    async fn get_published_complaints(
+        &self,
+        filter: &Filter,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<ComplaintFeedItem>, ApiError> {
+        let limit = pagination.limit();
+        let (conditions, mut args) = filter.build_for_sqlx(COMPLAINT_FILTER_FIELDS, 1)?;
+
+        let total_items = if pagination.include_total {
+            let total: i64 = sqlx::query_scalar_with(
+                &format!(
+                    "SELECT COUNT(*) FROM complaints c
+                    INNER JOIN locations l ON l.id = c.location_id
+                    WHERE c.published = true AND {}",
+                    conditions
+                ),
+                to_pg_arguments(args.clone()),
+            )
+            .fetch_one(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+            Some(total as u64)
+        } else {
+            None
+        };
+
+        let after = match pagination.decode_cursor()? {
+            Some(cursor) => {
+                let (condition, cursor_args) =
+                    cursor.build_for_sqlx("c.created_at", "c.id", SortOrder::Desc, args.len() + 1);
+                args.extend(cursor_args);
+                condition
+            }
+            None => "TRUE".to_string(),
+        };
+        args.push(Value::from(limit + 1));
+
+        let rows = sqlx::query_as_with::<_, ComplaintFeedRow, _>(
+            &format!(
+                "SELECT c.*,
+                    d.name AS driver_name,
+                    d.license_plate AS driver_license_plate,
+                    l.country AS location_country,
+                    l.state AS location_state,
+                    ci.id AS image_id,
+                    ci.image_key AS image_key,
+                    ci.content_type AS image_content_type,
+                    ci.size_bytes AS image_size_bytes,
+                    ci.sha256 AS image_sha256,
+                    ci.thumbnail_key AS image_thumbnail_key,
+                    ci.width AS image_width,
+                    ci.height AS image_height,
+                    ci.redacted_at AS image_redacted_at
+                FROM complaints c
+                INNER JOIN drivers d ON d.id = c.driver_id
+                INNER JOIN locations l ON l.id = c.location_id
+                LEFT JOIN LATERAL (
+                    SELECT * FROM complaint_images
+                    WHERE complaint_id = c.id AND processing_status = 'ready'
+                    ORDER BY id
+                    LIMIT 1
+                ) ci ON true
+                WHERE c.published = true
+                AND {} AND {}
+                ORDER BY c.created_at DESC, c.id DESC
+                LIMIT ${}",
+                conditions,
+                after,
+                args.len()
+            ),
+            to_pg_arguments(args),
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        let items = rows.into_iter().map(ComplaintFeedItem::from).collect();
+
+        Ok(CursorPage::new(items, limit, total_items, |item| {
+            Cursor::new(
+                item.complaint.complaint.created_at,
+                item.complaint.complaint.id,
+            )
+        }))
+    }
+
+    async fn get_complaints_for_driver_by_cursor(
+        &self,
+        driver_id: i32,
+        filter: &Filter,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<Complaint>, ApiError> {
+        let limit = pagination.limit();
+        let (conditions, mut args) = filter.build_for_sqlx(COMPLAINT_FILTER_FIELDS, 2)?;
+        args.insert(0, Value::from(driver_id));
+
+        let total_items = if pagination.include_total {
+            let total: i64 = sqlx::query_scalar_with(
+                &format!(
+                    "SELECT COUNT(*) FROM complaints c
+                    INNER JOIN locations l ON l.id = c.location_id
+                    WHERE c.driver_id = $1 AND c.published = true
+                    AND {}",
+                    conditions
+                ),
+                to_pg_arguments(args.clone()),
+            )
+            .fetch_one(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?;
+            Some(total as u64)
+        } else {
+            None
+        };
+
+        let after = match pagination.decode_cursor()? {
+            Some(cursor) => {
+                let (condition, cursor_args) =
+                    cursor.build_for_sqlx("c.created_at", "c.id", SortOrder::Desc, args.len() + 1);
+                args.extend(cursor_args);
+                condition
+            }
+            None => "TRUE".to_string(),
+        };
+        args.push(Value::from(limit + 1));
+
+        let complaints = sqlx::query_as_with::<_, Complaint, _>(
+            &format!(
+                "SELECT c.* FROM complaints c
+                INNER JOIN locations l ON l.id = c.location_id
+                WHERE c.driver_id = $1 AND c.published = true
+                AND {} AND {}
+                ORDER BY c.created_at DESC, c.id DESC
+                LIMIT ${}",
+                conditions,
+                after,
+                args.len()
+            ),
+            to_pg_arguments(args),
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(CursorPage::new(complaints, limit, total_items, |c| {
+            Cursor::new(c.created_at, c.id)
+        }))
     }
 
     // Complaint Image operations
@@ -218,14 +1031,7 @@ impl DBRepository for PostgresRepository {
         &self,
         complaint_image: &ComplaintImage,
     ) -> Result<ComplaintImage, ApiError> {
-        sqlx::query_as::<_, ComplaintImage>(
-            "INSERT INTO complaint_images (complaint_id, image_url) VALUES ($1, $2) RETURNING *",
-        )
-        .bind(complaint_image.complaint_id)
-        .bind(&complaint_image.image_url)
-        .fetch_one(&*self.pg_pool)
-        .await
-        .map_err(ApiError::DatabaseError)
+        insert_complaint_image(&*self.pg_pool, complaint_image).await
     }
 
     async fn get_complaint_images(
@@ -236,9 +1042,9 @@ impl DBRepository for PostgresRepository {
         let offset = (pagination.page - 1) * pagination.per_page;
 
         let images = sqlx::query_as::<_, ComplaintImage>(
-            "SELECT * FROM complaint_images 
-            WHERE complaint_id = $1
-            ORDER BY id 
+            "SELECT * FROM complaint_images
+            WHERE complaint_id = $1 AND processing_status = 'ready'
+            ORDER BY id
             LIMIT $2 OFFSET $3",
         )
         .bind(complaint_id)
@@ -249,7 +1055,9 @@ impl DBRepository for PostgresRepository {
         .map_err(ApiError::DatabaseError)?;
 
         let total_items: i64 =
-            sqlx::query_scalar("SELECT COUNT(*) FROM complaint_images WHERE complaint_id = $1")
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM complaint_images WHERE complaint_id = $1 AND processing_status = 'ready'",
+            )
                 .bind(complaint_id)
                 .fetch_one(&*self.pg_pool)
                 .await
@@ -263,6 +1071,49 @@ impl DBRepository for PostgresRepository {
         ))
     }
 
+    async fn get_complaint_image_by_id(&self, id: i32) -> Result<ComplaintImage, ApiError> {
+        sqlx::query_as::<_, ComplaintImage>("SELECT * FROM complaint_images WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)?
+            .ok_or_else(|| ApiError::NotFound(format!("Complaint image {} not found", id)))
+    }
+
+    async fn save_complaint_image_redaction(
+        &self,
+        id: i32,
+        redaction: &ComplaintImageRedaction,
+    ) -> Result<ComplaintImage, ApiError> {
+        // The perceptual hash is left alone, so reposts of the original still match
+        sqlx::query_as::<_, ComplaintImage>(
+            "UPDATE complaint_images SET image_key = $2, thumbnail_key = $3, width = $4,
+                height = $5, content_type = $6, size_bytes = $7, sha256 = $8,
+                original_key = $9, redactions = $10::jsonb, redacted_by = $11,
+                redacted_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING *",
+        )
+        .bind(id)
+        .bind(&redaction.objects.image_key)
+        .bind(&redaction.objects.thumbnail_key)
+        .bind(redaction.objects.width)
+        .bind(redaction.objects.height)
+        .bind(&redaction.objects.metadata.content_type)
+        .bind(redaction.objects.metadata.size_bytes)
+        .bind(&redaction.objects.metadata.sha256)
+        .bind(&redaction.original_key)
+        .bind(
+            serde_json::to_string(&redaction.regions)
+                .map_err(|e| ApiError::UnexpectedError(e.to_string()))?,
+        )
+        .bind(&redaction.redacted_by)
+        .fetch_optional(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?
+        .ok_or_else(|| ApiError::NotFound(format!("Complaint image {} not found", id)))
+    }
+
     async fn delete_complaint_image(&self, id: i32) -> Result<(), ApiError> {
         sqlx::query("DELETE FROM complaint_images WHERE id = $1")
             .bind(id)
@@ -353,43 +1204,128 @@ impl DBRepository for PostgresRepository {
     async fn search_drivers(
         &self,
         query: &str,
+        mode: PlateMatchMode,
+        filter: &Filter,
+        sort: &Sort<DriverSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Driver>, ApiError> {
         let offset = (pagination.page - 1) * pagination.per_page;
+        let (conditions, mut args) = filter.build_for_sqlx(COMPLAINT_FILTER_FIELDS, 3)?;
+        args.insert(0, Value::from(format!("%{}%", query)));
+        args.insert(1, Value::from(query));
+        // Names and plates containing the query always match, misread plates
+        // only in fuzzy mode
+        let driver_condition = match mode {
+            PlateMatchMode::Exact => "(d.name ILIKE $1 OR d.license_plate ILIKE $1)".to_string(),
+            PlateMatchMode::Fuzzy => format!(
+                "(d.name ILIKE $1 OR d.license_plate ILIKE $1 OR {})",
+                plate_condition(mode, 2)
+            ),
+        };
 
-        let drivers = sqlx::query_as::<_, Driver>(
-            "SELECT DISTINCT d.* FROM drivers d
-            INNER JOIN complaints c ON d.id = c.driver_id
-            WHERE (d.name ILIKE $1 OR d.license_plate ILIKE $1)
-            AND c.published = true
-            ORDER BY d.name 
-            LIMIT $2 OFFSET $3",
+        let total_items: i64 = sqlx::query_scalar_with(
+            &format!(
+                "SELECT COUNT(DISTINCT d.id) FROM drivers d
+                INNER JOIN complaints c ON d.id = c.driver_id
+                INNER JOIN locations l ON l.id = c.location_id
+                WHERE {}
+                AND c.published = true
+                AND {}",
+                driver_condition, conditions
+            ),
+            to_pg_arguments(args.clone()),
+        )
+        .fetch_one(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        let limit_placeholder = args.len() + 1;
+        args.push(Value::from(pagination.per_page));
+        args.push(Value::from(offset));
+
+        let drivers = sqlx::query_as_with::<_, Driver, _>(
+            &format!(
+                "SELECT d.* FROM drivers d
+                INNER JOIN complaints c ON d.id = c.driver_id
+                INNER JOIN locations l ON l.id = c.location_id
+                WHERE {}
+                AND c.published = true
+                AND {}
+                GROUP BY d.id
+                ORDER BY {}
+                LIMIT ${} OFFSET ${}",
+                driver_condition,
+                conditions,
+                driver_order_by(sort),
+                limit_placeholder,
+                limit_placeholder + 1
+            ),
+            to_pg_arguments(args),
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(PaginatedRecord::new(
+            drivers,
+            total_items as u64,
+            pagination.page,
+            pagination.per_page,
         )
-        .bind(format!("%{}%", query))
+        .with_sort(Some(sort.applied())))
+    }
+
+    async fn search_drivers_by_license_plate(
+        &self,
+        license_plate: &str,
+        mode: PlateMatchMode,
+        pagination: &Pagination,
+    ) -> Result<PaginatedRecord<PlateMatch>, ApiError> {
+        let offset = (pagination.page - 1) * pagination.per_page;
+
+        let condition = plate_condition(mode, 1);
+
+        let matches = sqlx::query_as::<_, PlateMatch>(&format!(
+            "SELECT d.*,
+                similarity(normalize_plate(d.license_plate), normalize_plate($1)) AS similarity,
+                levenshtein(normalize_plate(d.license_plate), normalize_plate($1)) AS distance
+            FROM drivers d
+            WHERE {}
+            AND EXISTS (
+                SELECT 1 FROM complaints c WHERE c.driver_id = d.id AND c.published = true
+            )
+            ORDER BY distance, similarity DESC, d.name
+            LIMIT $2 OFFSET $3",
+            condition
+        ))
+        .bind(license_plate)
         .bind(pagination.per_page as i64)
         .bind(offset as i64)
         .fetch_all(&*self.pg_pool)
         .await
         .map_err(ApiError::DatabaseError)?;
 
-        let total_items: i64 = sqlx::query_scalar(
-            "SELECT COUNT(DISTINCT d.id) FROM drivers d
-            INNER JOIN complaints c ON d.id = c.driver_id
-            WHERE (d.name ILIKE $1 OR d.license_plate ILIKE $1)
-            AND c.published = true",
-        )
-        .bind(format!("%{}%", query))
+        let total_items: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM drivers d
+            WHERE {}
+            AND EXISTS (
+                SELECT 1 FROM complaints c WHERE c.driver_id = d.id AND c.published = true
+            )",
+            condition
+        ))
+        .bind(license_plate)
         .fetch_one(&*self.pg_pool)
         .await
         .map_err(ApiError::DatabaseError)?;
 
         Ok(PaginatedRecord::new(
-            drivers,
+            matches,
             total_items as u64,
             pagination.page,
             pagination.per_page,
         ))
     }
+
     async fn get_driver_by_name_and_license_plate(
         &self,
         name: &str,
@@ -408,4 +1344,243 @@ impl DBRepository for PostgresRepository {
                 _ => ApiError::DatabaseError(err),
             })
     }
+
+    // Moderation operations
+    async fn is_moderator(&self, user_id: &str) -> Result<bool, ApiError> {
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM moderators WHERE user_id = $1)")
+            .bind(user_id)
+            .fetch_one(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)
+    }
+
+    async fn is_verified_reporter(&self, user_id: &str) -> Result<bool, ApiError> {
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM verified_reporters WHERE user_id = $1)")
+            .bind(user_id)
+            .fetch_one(&*self.pg_pool)
+            .await
+            .map_err(ApiError::DatabaseError)
+    }
+
+    async fn get_moderation_rules(&self) -> Result<Vec<ModerationRule>, ApiError> {
+        let rows = sqlx::query_as::<_, ModerationRuleRow>(
+            "SELECT id, name, conditions::text AS conditions, action, reason
+            FROM moderation_rules WHERE enabled ORDER BY id",
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        // A broken rule is skipped rather than blocking every submission
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| match serde_json::from_str(&row.conditions) {
+                Ok(conditions) => Some(ModerationRule {
+                    id: row.id,
+                    name: row.name,
+                    conditions,
+                    action: row.action,
+                    reason: row.reason,
+                }),
+                Err(e) => {
+                    log::warn!("Skipping moderation rule '{}': {}", row.name, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn get_pending_complaints(
+        &self,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<Complaint>, ApiError> {
+        let limit = pagination.limit();
+
+        let total_items = if pagination.include_total {
+            let total: i64 =
+                sqlx::query_scalar("SELECT COUNT(*) FROM complaints WHERE published = false")
+                    .fetch_one(&*self.pg_pool)
+                    .await
+                    .map_err(ApiError::DatabaseError)?;
+            Some(total as u64)
+        } else {
+            None
+        };
+
+        let mut args = Vec::new();
+        let after = match pagination.decode_cursor()? {
+            Some(cursor) => {
+                let (condition, cursor_args) =
+                    cursor.build_for_sqlx("created_at", "id", SortOrder::Asc, 1);
+                args.extend(cursor_args);
+                condition
+            }
+            None => "TRUE".to_string(),
+        };
+        args.push(Value::from(limit + 1));
+
+        // Oldest first, so the queue is worked through in submission order
+        let complaints = sqlx::query_as_with::<_, Complaint, _>(
+            &format!(
+                "SELECT * FROM complaints
+                WHERE published = false AND {}
+                ORDER BY created_at, id
+                LIMIT ${}",
+                after,
+                args.len()
+            ),
+            to_pg_arguments(args),
+        )
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(CursorPage::new(complaints, limit, total_items, |c| {
+            Cursor::new(c.created_at, c.id)
+        }))
+    }
+
+    async fn find_duplicate_images(
+        &self,
+        complaint_ids: &[i32],
+        max_distance: u32,
+        window: Duration,
+    ) -> Result<HashMap<i32, Vec<DuplicateImage>>, ApiError> {
+        // Both branches of the candidate filters are served by the indexes of
+        // migration 019, so only recent images are compared bit by bit
+        let duplicates = sqlx::query_as::<_, DuplicateImage>(&format!(
+            "WITH images AS (
+                SELECT ci.id, ci.complaint_id, c.driver_id, c.reporter_id, ci.perceptual_hash
+                FROM complaint_images ci
+                JOIN complaints c ON c.id = ci.complaint_id
+                WHERE ci.complaint_id = ANY($1) AND ci.perceptual_hash IS NOT NULL
+            ),
+            candidates AS (
+                SELECT ci.id, ci.complaint_id, c.driver_id, c.reporter_id, ci.perceptual_hash
+                FROM complaint_images ci
+                JOIN complaints c ON c.id = ci.complaint_id
+                WHERE ci.perceptual_hash IN (SELECT perceptual_hash FROM images)
+                OR (ci.perceptual_hash IS NOT NULL
+                    AND ci.created_at > CURRENT_TIMESTAMP - make_interval(secs => $3))
+                UNION ALL
+                SELECT di.id, NULL, di.driver_id, NULL, di.perceptual_hash
+                FROM driver_images di
+                WHERE di.perceptual_hash IN (SELECT perceptual_hash FROM images)
+                OR (di.perceptual_hash IS NOT NULL
+                    AND di.created_at > CURRENT_TIMESTAMP - make_interval(secs => $3))
+            )
+            SELECT i.id AS image_id, i.complaint_id,
+                d.id AS duplicate_image_id, d.complaint_id AS duplicate_complaint_id,
+                d.driver_id AS duplicate_driver_id, {} AS distance
+            FROM images i
+            JOIN candidates d ON (
+                d.driver_id <> i.driver_id
+                OR (d.complaint_id IS NOT NULL AND d.reporter_id IS DISTINCT FROM i.reporter_id)
+            ) AND {} <= $2
+            ORDER BY i.complaint_id, distance, d.id",
+            hamming_distance("i.perceptual_hash", "d.perceptual_hash"),
+            hamming_distance("i.perceptual_hash", "d.perceptual_hash"),
+        ))
+        .bind(complaint_ids)
+        .bind(max_distance as i32)
+        .bind(window.as_secs_f64())
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        let mut duplicates_by_complaint: HashMap<i32, Vec<DuplicateImage>> =
+            complaint_ids.iter().map(|id| (*id, Vec::new())).collect();
+        for duplicate in duplicates {
+            duplicates_by_complaint
+                .entry(duplicate.complaint_id)
+                .or_default()
+                .push(duplicate);
+        }
+
+        Ok(duplicates_by_complaint)
+    }
+
+    async fn get_complaints_sharing_image(
+        &self,
+        image_id: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SharedImageComplaint>, ApiError> {
+        sqlx::query_as::<_, SharedImageComplaint>(&format!(
+            "SELECT c.*, ci.id AS image_id, {} AS distance
+            FROM complaint_images source
+            JOIN complaint_images ci ON ci.id <> source.id AND {} <= $2
+            JOIN complaints c ON c.id = ci.complaint_id
+            WHERE source.id = $1
+            ORDER BY distance, c.created_at, c.id",
+            hamming_distance("source.perceptual_hash", "ci.perceptual_hash"),
+            hamming_distance("source.perceptual_hash", "ci.perceptual_hash"),
+        ))
+        .bind(image_id)
+        .bind(max_distance as i32)
+        .fetch_all(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+
+    async fn get_idempotency_record(
+        &self,
+        submitter: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<IdempotencyRecord>, ApiError> {
+        // Keys are claimed and answered in the same transaction, so any row
+        // visible here has its response
+        sqlx::query_as::<_, IdempotencyRecord>(&format!(
+            "SELECT request_hash, response::text AS response
+            FROM idempotency_keys
+            WHERE submitter = $1 AND idempotency_key = $2
+            AND created_at >= CURRENT_TIMESTAMP - INTERVAL '{}'",
+            IDEMPOTENCY_KEY_TTL
+        ))
+        .bind(submitter)
+        .bind(idempotency_key)
+        .fetch_optional(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRepository {
+    async fn hit(&self, key: &str, window: Duration) -> Result<RateLimitHit, ApiError> {
+        let (window_start, resets_at) = current_window(window);
+        let hits: i32 = sqlx::query_scalar(
+            "INSERT INTO rate_limit_counters (counter_key, window_start, resets_at, hits)
+            VALUES ($1, $2, $3, 1)
+            ON CONFLICT (counter_key) DO UPDATE
+            SET hits = CASE
+                    WHEN rate_limit_counters.window_start = EXCLUDED.window_start
+                    THEN rate_limit_counters.hits + 1
+                    ELSE 1
+                END,
+                window_start = EXCLUDED.window_start,
+                resets_at = EXCLUDED.resets_at
+            RETURNING hits",
+        )
+        .bind(key)
+        .bind(window_start)
+        .bind(resets_at)
+        .fetch_one(&*self.pg_pool)
+        .await
+        .map_err(ApiError::DatabaseError)?;
+
+        Ok(RateLimitHit {
+            hits: hits as u32,
+            resets_at,
+        })
+    }
+
+    async fn prune(&self) -> Result<u64, ApiError> {
+        let result =
+            sqlx::query("DELETE FROM rate_limit_counters WHERE resets_at <= CURRENT_TIMESTAMP")
+                .execute(&*self.pg_pool)
+                .await
+                .map_err(ApiError::DatabaseError)?;
+
+        Ok(result.rows_affected())
+    }
 }
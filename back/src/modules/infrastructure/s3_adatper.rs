@@ -3,18 +3,51 @@ use std::time::Duration;
 
 use crate::error::ApiError;
 use crate::modules::port::BucketPort;
+use crate::modules::{BucketObject, ObjectMetadata, PresignedPost, UploadPolicy};
 use crate::utils::s3::S3Repository;
 
 #[async_trait]
 impl BucketPort for S3Repository {
-    async fn generate_upload_url(&self, key: &str) -> Result<(String, String), ApiError> {
-        let presigned_url = self
-            .post_presigned_url(key, Duration::from_secs(3600))
-            .await?;
+    async fn generate_upload_url(
+        &self,
+        key: &str,
+        policy: &UploadPolicy,
+    ) -> Result<PresignedPost, ApiError> {
+        self.presigned_post(key, policy).await
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, ApiError> {
+        S3Repository::get_object(self, key).await
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), ApiError> {
+        S3Repository::put_object(self, key, bytes, content_type).await
+    }
 
-        let public_url = format!("https://{}.s3.amazonaws.com/{}", self.bucket, key);
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, ApiError> {
+        S3Repository::list_objects(self, prefix).await
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        S3Repository::public_url(self, key)
+    }
+
+    async fn signed_url(
+        &self,
+        key: &str,
+        signed_at: chrono::DateTime<chrono::Utc>,
+        expires_in: Duration,
+    ) -> Result<String, ApiError> {
+        self.presigned_get(key, signed_at, expires_in).await
+    }
 
-        Ok((presigned_url, public_url))
+    async fn object_metadata(&self, key: &str) -> Result<ObjectMetadata, ApiError> {
+        self.head_object(key).await
     }
 
     async fn delete_image(&self, key: &str) -> Result<(), ApiError> {
@@ -4,6 +4,7 @@ pub use model::*;
 pub mod port;
 
 pub mod infrastructure;
+mod moderation_rules;
 mod service;
 pub use service::*;
 
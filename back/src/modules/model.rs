@@ -1,13 +1,45 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-use crate::utils::database::PaginatedRecord;
+use crate::utils::database::{Filter, FilterCondition, PaginatedRecord, SortField, SortOrder};
 
-#[derive(Clone, Debug, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "country", rename_all = "snake_case")]
-pub enum Country {
-    Peru,
-    Mexico,
+// Types the utilities work with are defined next to them, so utils does not
+// depend on modules
+pub use crate::utils::{
+    bucket::{BucketObject, ObjectMetadata, PresignedPost, UploadPolicy},
+    image_processing::RedactionRegion,
+    pii::PiiKind,
+    profanity::ProfanitySeverity,
+    rate_limit::RateLimitHit,
+    Country,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "complaint_category", rename_all = "snake_case")]
+pub enum ComplaintCategory {
+    Harassment,
+    Overcharging,
+    RecklessDriving,
+    RouteDeviation,
+    Theft,
+    Assault,
+    #[default]
+    Other,
+}
+
+impl ComplaintCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComplaintCategory::Harassment => "harassment",
+            ComplaintCategory::Overcharging => "overcharging",
+            ComplaintCategory::RecklessDriving => "reckless_driving",
+            ComplaintCategory::RouteDeviation => "route_deviation",
+            ComplaintCategory::Theft => "theft",
+            ComplaintCategory::Assault => "assault",
+            ComplaintCategory::Other => "other",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -34,19 +66,210 @@ impl Driver {
     }
 }
 
+/// How a license plate lookup should match stored plates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlateMatchMode {
+    /// Only plates equal to the query once separators and case are ignored
+    Exact,
+    /// Also plates that differ by commonly misread characters (0/O, 1/I, 8/B...)
+    /// or a missing/extra character, ranked by similarity
+    #[default]
+    Fuzzy,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PlateMatch {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub driver: Driver,
+    /// Trigram similarity between the normalized plates, from 0 to 1
+    pub similarity: f32,
+    /// Edit distance between the normalized plates
+    pub distance: i32,
+}
+
+/// Outcome of a garbage collection run over unreferenced uploads
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrphanedUploadsReport {
+    pub dry_run: bool,
+    /// Objects older than the grace period looked at
+    pub scanned: usize,
+    /// Objects no image references. In a dry run, none of them is deleted.
+    pub orphaned: Vec<BucketObject>,
+    pub deleted: usize,
+    pub failed: usize,
+}
+
+/// What an upload is for, which decides where it is stored and what it may contain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadPurpose {
+    DriverPhoto,
+    ComplaintEvidence,
+    ClaimDocument,
+}
+
+impl UploadPurpose {
+    pub const ALL: [UploadPurpose; 3] = [
+        UploadPurpose::DriverPhoto,
+        UploadPurpose::ComplaintEvidence,
+        UploadPurpose::ClaimDocument,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadPurpose::DriverPhoto => "driver_photo",
+            UploadPurpose::ComplaintEvidence => "complaint_evidence",
+            UploadPurpose::ClaimDocument => "claim_document",
+        }
+    }
+
+    /// Key prefix objects uploaded for this purpose are stored under
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            UploadPurpose::DriverPhoto => "driver_images",
+            UploadPurpose::ComplaintEvidence => "complaint_images",
+            UploadPurpose::ClaimDocument => "claim_documents",
+        }
+    }
+
+    pub fn allowed_content_types(&self) -> &'static [&'static str] {
+        const IMAGES: &[&str] = &["image/jpeg", "image/png", "image/webp"];
+        const DOCUMENTS: &[&str] = &["application/pdf", "image/jpeg", "image/png"];
+        match self {
+            UploadPurpose::DriverPhoto | UploadPurpose::ComplaintEvidence => IMAGES,
+            UploadPurpose::ClaimDocument => DOCUMENTS,
+        }
+    }
+
+    pub fn max_size_bytes(&self) -> i64 {
+        match self {
+            UploadPurpose::DriverPhoto => 5 * 1024 * 1024,
+            UploadPurpose::ComplaintEvidence => 10 * 1024 * 1024,
+            UploadPurpose::ClaimDocument => 15 * 1024 * 1024,
+        }
+    }
+
+    /// How long the upload URL stays valid. Uploads start right after the
+    /// URL is requested, so this only needs to cover slow connections.
+    pub fn expires_in(&self) -> std::time::Duration {
+        match self {
+            UploadPurpose::DriverPhoto | UploadPurpose::ComplaintEvidence => {
+                std::time::Duration::from_secs(5 * 60)
+            }
+            UploadPurpose::ClaimDocument => std::time::Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Purpose of an object, from its key prefix
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|purpose| {
+            key.strip_prefix(purpose.prefix())
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// A presigned upload along with the key to reference the object by afterwards
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresignedUpload {
+    pub upload_url: String,
+    pub fields: std::collections::BTreeMap<String, String>,
+    pub key: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Where an image is in the processing pipeline. Only `Ready` images are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "image_processing_status", rename_all = "snake_case")]
+pub enum ImageProcessingStatus {
+    #[default]
+    Pending,
+    Processing,
+    Ready,
+    Failed,
+}
+
+/// Which table an image belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageKind {
+    Driver,
+    Complaint,
+}
+
+/// An image claimed by the processing pipeline
+#[derive(Debug, Clone)]
+pub struct PendingImage {
+    pub kind: ImageKind,
+    pub id: i32,
+    pub image_key: String,
+}
+
+/// Objects produced by processing an image, replacing the original upload
+#[derive(Debug, Clone)]
+pub struct ProcessedImageObjects {
+    pub image_key: String,
+    pub thumbnail_key: String,
+    pub width: i32,
+    pub height: i32,
+    pub metadata: ObjectMetadata,
+    /// Perceptual hash of the image, its 64 bits stored as a signed integer
+    pub perceptual_hash: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct DriverImage {
     pub id: i32,
     pub driver_id: i32,
-    pub image_url: String,
+    /// Complaint the photo was submitted with
+    #[serde(skip)]
+    pub complaint_id: Option<i32>,
+    /// Whether that complaint is published, only read along with the image
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub published: bool,
+    /// Key of the object in the bucket
+    pub image_key: String,
+    pub content_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Hex-encoded SHA-256 of the object, when the bucket reports it
+    pub sha256: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub processing_status: ImageProcessingStatus,
+    /// Short-lived signed URL of the image, resolved from the key when read.
+    /// Only set once the image is processed.
+    #[sqlx(skip)]
+    pub image_url: Option<String>,
+    #[sqlx(skip)]
+    pub thumbnail_url: Option<String>,
 }
 
 impl DriverImage {
-    pub fn new(driver_id: i32, image_url: &str) -> Self {
+    pub fn new(
+        driver_id: i32,
+        complaint_id: i32,
+        image_key: &str,
+        metadata: ObjectMetadata,
+    ) -> Self {
         Self {
             id: 0,
             driver_id,
-            image_url: image_url.to_string(),
+            complaint_id: Some(complaint_id),
+            published: false,
+            image_key: image_key.to_string(),
+            content_type: metadata.content_type,
+            size_bytes: metadata.size_bytes,
+            sha256: metadata.sha256,
+            thumbnail_key: None,
+            width: None,
+            height: None,
+            processing_status: ImageProcessingStatus::Pending,
+            image_url: None,
+            thumbnail_url: None,
         }
     }
 }
@@ -58,7 +281,29 @@ pub struct Complaint {
     pub location_id: i32,
     pub taxi_application: String,
     pub description: String,
+    pub category: ComplaintCategory,
+    pub incident_at: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Set by moderators once the complaint has been reviewed
+    pub published: bool,
+    /// Earlier complaint against the same driver this one closely resembles
+    pub duplicate_of_id: Option<i32>,
+    /// Description as submitted, when personal data had to be masked in it.
+    /// Only shown to moderators.
+    #[serde(skip_serializing)]
+    pub original_description: Option<String>,
+    /// Kinds of personal data masked in the description
+    pub pii_kinds: Vec<PiiKind>,
+    /// Worst profanity found in the description or driver name
+    pub profanity_severity: Option<ProfanitySeverity>,
+    /// Auth user who submitted the complaint, when signed in. Never shown.
+    #[serde(skip_serializing)]
+    pub reporter_id: Option<String>,
+    /// Hex SHA-256 of `NewComplaint::submitter`. Never shown.
+    #[serde(skip)]
+    pub submitter_hash: Option<String>,
+    /// Names of the moderation rules that matched the complaint on submission
+    pub fired_rules: Vec<String>,
 }
 
 impl Complaint {
@@ -74,28 +319,292 @@ impl Complaint {
             location_id,
             taxi_application: taxi_application.to_string(),
             description: description.to_string(),
+            category: ComplaintCategory::default(),
+            incident_at: None,
             created_at: chrono::Utc::now(),
+            published: false,
+            duplicate_of_id: None,
+            original_description: None,
+            pii_kinds: Vec::new(),
+            profanity_severity: None,
+            reporter_id: None,
+            submitter_hash: None,
+            fired_rules: Vec::new(),
         }
     }
 }
 
+/// What a moderation rule does to the complaints it matches. When several
+/// rules match, the most restrictive action wins.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "moderation_action", rename_all = "snake_case")]
+pub enum ModerationAction {
+    Publish,
+    /// Wait for a moderator, as complaints no rule publishes do
+    Hold,
+    /// Refuse the submission
+    Reject,
+}
+
+/// A condition on a submitted complaint, stored as JSON such as
+/// `{"type": "description_shorter_than", "length": 20}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// Fewer characters than `length` once surrounding whitespace is trimmed
+    DescriptionShorterThan {
+        length: usize,
+    },
+    ContainsPii,
+    Profanity {
+        min_severity: ProfanitySeverity,
+    },
+    ReporterVerified,
+    CategoryIn {
+        categories: Vec<ComplaintCategory>,
+    },
+    /// Resembles a recent complaint against the same driver
+    PossibleDuplicate,
+    HasImages,
+    Not {
+        condition: Box<RuleCondition>,
+    },
+    /// At least one of `conditions`
+    Any {
+        conditions: Vec<RuleCondition>,
+    },
+}
+
+/// A rule applying `action` to the complaints meeting all its conditions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModerationRule {
+    pub id: i32,
+    pub name: String,
+    pub conditions: Vec<RuleCondition>,
+    pub action: ModerationAction,
+    /// Told to the reporter when the rule rejects a complaint
+    pub reason: Option<String>,
+}
+
+/// What moderation rules know about a submitted complaint
+#[derive(Clone, Debug)]
+pub struct ComplaintFacts {
+    pub description_length: usize,
+    pub category: ComplaintCategory,
+    pub contains_pii: bool,
+    pub profanity_severity: Option<ProfanitySeverity>,
+    pub reporter_verified: bool,
+    pub possible_duplicate: bool,
+    pub image_count: usize,
+}
+
+/// Sort options for complaint listings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplaintSortField {
+    /// Order in which complaints were submitted
+    #[default]
+    Submitted,
+    /// Date the incident happened, falling back to the submission date
+    IncidentDate,
+    /// Severity of the complaint category
+    Severity,
+}
+
+impl SortField for ComplaintSortField {
+    fn name(&self) -> &'static str {
+        match self {
+            ComplaintSortField::Submitted => "submitted",
+            ComplaintSortField::IncidentDate => "incident_date",
+            ComplaintSortField::Severity => "severity",
+        }
+    }
+
+    fn default_order(&self) -> SortOrder {
+        match self {
+            ComplaintSortField::Submitted => SortOrder::Asc,
+            ComplaintSortField::IncidentDate | ComplaintSortField::Severity => SortOrder::Desc,
+        }
+    }
+}
+
+/// Sort options for driver searches, computed over the driver's published
+/// complaints that match the search filters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriverSortField {
+    #[default]
+    Alphabetical,
+    MostComplaints,
+    MostRecentComplaint,
+    HighestSeverity,
+}
+
+impl SortField for DriverSortField {
+    fn name(&self) -> &'static str {
+        match self {
+            DriverSortField::Alphabetical => "alphabetical",
+            DriverSortField::MostComplaints => "most_complaints",
+            DriverSortField::MostRecentComplaint => "most_recent_complaint",
+            DriverSortField::HighestSeverity => "highest_severity",
+        }
+    }
+
+    fn default_order(&self) -> SortOrder {
+        match self {
+            DriverSortField::Alphabetical => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+/// Filters shared by the endpoints that list or search complaints. Conditions
+/// apply to published complaints; for driver searches a driver matches when at
+/// least one of their complaints does.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ComplaintFilters {
+    pub location_id: Option<i32>,
+    pub country: Option<Country>,
+    pub state: Option<String>,
+    pub taxi_application: Option<String>,
+    pub category: Option<ComplaintCategory>,
+    pub incident_from: Option<chrono::DateTime<chrono::Utc>>,
+    pub incident_to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ComplaintFilters {
+    pub fn to_filter(&self) -> Filter {
+        let mut filter = Filter::new();
+
+        if let Some(location_id) = self.location_id {
+            filter.add("location_id", FilterCondition::eq(location_id));
+        }
+        if let Some(country) = &self.country {
+            filter.add("country", FilterCondition::eq(country.as_str()));
+        }
+        if let Some(state) = &self.state {
+            filter.add("state", FilterCondition::eq(state.as_str()));
+        }
+        if let Some(taxi_application) = &self.taxi_application {
+            filter.add(
+                "taxi_application",
+                FilterCondition::eq(taxi_application.as_str()),
+            );
+        }
+        if let Some(category) = &self.category {
+            filter.add("category", FilterCondition::eq(category.as_str()));
+        }
+        match (self.incident_from, self.incident_to) {
+            (Some(from), Some(to)) => {
+                filter.add("incident_at", FilterCondition::between(from, to));
+            }
+            (Some(from), None) => {
+                filter.add("incident_at", FilterCondition::gte(from));
+            }
+            (None, Some(to)) => {
+                filter.add("incident_at", FilterCondition::lte(to));
+            }
+            (None, None) => {}
+        }
+
+        filter
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ComplaintImage {
     pub id: i32,
     pub complaint_id: i32,
-    pub image_url: String,
+    /// Key of the object in the bucket
+    pub image_key: String,
+    pub content_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Hex-encoded SHA-256 of the object, when the bucket reports it
+    pub sha256: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub processing_status: ImageProcessingStatus,
+    /// Short-lived signed URL of the image, only handed out once the image is
+    /// processed and the complaint is published, or to moderators
+    #[sqlx(skip)]
+    pub image_url: Option<String>,
+    #[sqlx(skip)]
+    pub thumbnail_url: Option<String>,
+    /// Private key of the unredacted image, never exposed through the API
+    #[serde(skip)]
+    pub original_key: Option<String>,
+    pub redacted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl ComplaintImage {
-    pub fn new(complaint_id: i32, image_url: &str) -> Self {
+    pub fn new(complaint_id: i32, image_key: &str, metadata: ObjectMetadata) -> Self {
         Self {
             id: 0,
             complaint_id,
-            image_url: image_url.to_string(),
+            image_key: image_key.to_string(),
+            content_type: metadata.content_type,
+            size_bytes: metadata.size_bytes,
+            sha256: metadata.sha256,
+            thumbnail_key: None,
+            width: None,
+            height: None,
+            processing_status: ImageProcessingStatus::Pending,
+            image_url: None,
+            thumbnail_url: None,
+            original_key: None,
+            redacted_at: None,
         }
     }
 }
 
+/// A redacted copy of a complaint image replacing its public objects
+#[derive(Debug, Clone)]
+pub struct ComplaintImageRedaction {
+    pub original_key: String,
+    pub regions: Vec<RedactionRegion>,
+    pub redacted_by: String,
+    pub objects: ProcessedImageObjects,
+}
+
+/// Endpoints that can be configured to require a CAPTCHA. Flagging and
+/// corroborating complaints have no endpoints yet; each gets a variant and a
+/// toggle once it does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptchaEndpoint {
+    CreateComplaint,
+}
+
+/// Client-chosen key making a request safe to retry, with a hash of the
+/// request it was sent with
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    /// Who sent the request, the only one its key is replayed to
+    pub submitter: String,
+    pub key: String,
+    pub request_hash: String,
+}
+
+/// A request previously made with an idempotency key
+#[derive(Debug, FromRow)]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    /// JSON response, saved along with whatever the request wrote
+    pub response: Option<String>,
+}
+
+/// The outcome of a request made with an optional idempotency key
+#[derive(Debug)]
+pub enum Idempotent<T> {
+    /// The request was processed now
+    Fresh(T),
+    /// The response to an earlier request with the same key
+    Replayed(T),
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewComplaint {
     pub description: String,
@@ -103,8 +612,17 @@ pub struct NewComplaint {
     pub taxi_license_plate: String,
     pub location_id: i32,
     pub taxi_application: String,
+    pub category: ComplaintCategory,
+    pub incident_at: Option<chrono::DateTime<chrono::Utc>>,
     pub driver_image: Option<String>,
     pub complaint_images: Option<Vec<String>>,
+    /// Auth user submitting the complaint, not part of the request itself
+    #[serde(skip)]
+    pub reporter_id: Option<String>,
+    /// Who is submitting the complaint: the reporter when signed in, the client
+    /// IP otherwise. Not part of the request itself either.
+    #[serde(skip)]
+    pub submitter: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -124,4 +642,65 @@ pub struct DriverWithImages {
 pub struct ComplaintWithImages {
     pub complaint: Complaint,
     pub images: Vec<ComplaintImage>,
+    /// Description as submitted, for moderators
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_description: Option<String>,
+}
+
+/// An image resembling one attached to a complaint, but belonging to another driver
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DuplicateImage {
+    /// Image of the complaint being moderated
+    pub image_id: i32,
+    #[serde(skip)]
+    pub complaint_id: i32,
+    pub duplicate_image_id: i32,
+    /// Complaint the duplicate is attached to, or `None` for a driver photo
+    pub duplicate_complaint_id: Option<i32>,
+    pub duplicate_driver_id: i32,
+    /// Bits that differ between the perceptual hashes, 0 for the same photo
+    pub distance: i32,
+}
+
+/// A recent complaint against a driver whose description resembles the one of
+/// a new complaint
+#[derive(Debug, Clone, FromRow)]
+pub struct SimilarComplaint {
+    pub id: i32,
+    pub category: ComplaintCategory,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Trigram similarity between the descriptions, from 0 to 1
+    pub similarity: f32,
+    /// Whether it was submitted by the submitter of the new complaint
+    pub same_submitter: bool,
+}
+
+/// A pending complaint, flagged when its images were already seen elsewhere
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationQueueItem {
+    #[serde(flatten)]
+    pub complaint: Complaint,
+    /// Description as submitted, when personal data was masked in it
+    pub original_description: Option<String>,
+    pub duplicate_images: Vec<DuplicateImage>,
+}
+
+/// A complaint with an image resembling the one asked about
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SharedImageComplaint {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub complaint: Complaint,
+    /// The complaint's matching image
+    pub image_id: i32,
+    pub distance: i32,
+}
+
+/// A published complaint as shown in the public feed, with only its first image
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComplaintFeedItem {
+    #[serde(flatten)]
+    pub complaint: ComplaintWithImages,
+    pub driver: Driver,
+    pub location: Location,
 }
@@ -0,0 +1,250 @@
+use super::{ComplaintFacts, ModerationAction, ModerationRule, RuleCondition};
+
+/// The rules a complaint matched, and the action to take on it
+#[derive(Debug)]
+pub struct ModerationDecision<'a> {
+    /// Most restrictive action of the fired rules, none when no rule fired
+    pub action: Option<ModerationAction>,
+    pub fired: Vec<&'a ModerationRule>,
+}
+
+impl ModerationDecision<'_> {
+    /// Reason given by the first fired rule with the decided action
+    pub fn reason(&self) -> Option<&str> {
+        self.fired
+            .iter()
+            .filter(|rule| Some(rule.action) == self.action)
+            .find_map(|rule| rule.reason.as_deref())
+    }
+}
+
+/// Evaluates every rule against a complaint. A rule fires when all of its
+/// conditions hold, so a rule without conditions always fires.
+pub fn evaluate<'a>(rules: &'a [ModerationRule], facts: &ComplaintFacts) -> ModerationDecision<'a> {
+    let fired: Vec<&ModerationRule> = rules
+        .iter()
+        .filter(|rule| {
+            rule.conditions
+                .iter()
+                .all(|condition| matches(condition, facts))
+        })
+        .collect();
+
+    ModerationDecision {
+        action: fired.iter().map(|rule| rule.action).max(),
+        fired,
+    }
+}
+
+fn matches(condition: &RuleCondition, facts: &ComplaintFacts) -> bool {
+    match condition {
+        RuleCondition::DescriptionShorterThan { length } => facts.description_length < *length,
+        RuleCondition::ContainsPii => facts.contains_pii,
+        RuleCondition::Profanity { min_severity } => facts
+            .profanity_severity
+            .is_some_and(|severity| severity >= *min_severity),
+        RuleCondition::ReporterVerified => facts.reporter_verified,
+        RuleCondition::CategoryIn { categories } => categories.contains(&facts.category),
+        RuleCondition::PossibleDuplicate => facts.possible_duplicate,
+        RuleCondition::HasImages => facts.image_count > 0,
+        RuleCondition::Not { condition } => !matches(condition, facts),
+        RuleCondition::Any { conditions } => {
+            conditions.iter().any(|condition| matches(condition, facts))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{ComplaintCategory, ProfanitySeverity};
+
+    fn rule(
+        name: &str,
+        conditions: Vec<RuleCondition>,
+        action: ModerationAction,
+        reason: Option<&str>,
+    ) -> ModerationRule {
+        ModerationRule {
+            id: 0,
+            name: name.to_string(),
+            conditions,
+            action,
+            reason: reason.map(str::to_string),
+        }
+    }
+
+    fn facts() -> ComplaintFacts {
+        ComplaintFacts {
+            description_length: 100,
+            category: ComplaintCategory::Overcharging,
+            contains_pii: false,
+            profanity_severity: None,
+            reporter_verified: false,
+            possible_duplicate: false,
+            image_count: 0,
+        }
+    }
+
+    fn fired<'a>(decision: &ModerationDecision<'a>) -> Vec<&'a str> {
+        decision
+            .fired
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn no_rules_decide_nothing() {
+        let decision = evaluate(&[], &facts());
+        assert_eq!(decision.action, None);
+        assert!(decision.fired.is_empty());
+        assert_eq!(decision.reason(), None);
+    }
+
+    #[test]
+    fn rules_without_conditions_always_fire() {
+        let rules = [rule("always", vec![], ModerationAction::Publish, None)];
+        let decision = evaluate(&rules, &facts());
+        assert_eq!(decision.action, Some(ModerationAction::Publish));
+        assert_eq!(fired(&decision), ["always"]);
+    }
+
+    #[test]
+    fn rules_fire_when_all_conditions_hold() {
+        let rules = [rule(
+            "verified_overcharging",
+            vec![
+                RuleCondition::ReporterVerified,
+                RuleCondition::CategoryIn {
+                    categories: vec![ComplaintCategory::Overcharging],
+                },
+            ],
+            ModerationAction::Publish,
+            None,
+        )];
+        assert_eq!(evaluate(&rules, &facts()).action, None);
+
+        let verified = ComplaintFacts {
+            reporter_verified: true,
+            ..facts()
+        };
+        assert_eq!(
+            evaluate(&rules, &verified).action,
+            Some(ModerationAction::Publish)
+        );
+    }
+
+    #[test]
+    fn most_restrictive_action_wins() {
+        let rules = [
+            rule("publish", vec![], ModerationAction::Publish, None),
+            rule(
+                "hold_pii",
+                vec![RuleCondition::ContainsPii],
+                ModerationAction::Hold,
+                None,
+            ),
+            rule(
+                "reject_severe",
+                vec![RuleCondition::Profanity {
+                    min_severity: ProfanitySeverity::Severe,
+                }],
+                ModerationAction::Reject,
+                None,
+            ),
+        ];
+
+        let pii = ComplaintFacts {
+            contains_pii: true,
+            profanity_severity: Some(ProfanitySeverity::Mild),
+            ..facts()
+        };
+        let decision = evaluate(&rules, &pii);
+        assert_eq!(decision.action, Some(ModerationAction::Hold));
+        assert_eq!(fired(&decision), ["publish", "hold_pii"]);
+
+        let severe = ComplaintFacts {
+            profanity_severity: Some(ProfanitySeverity::Severe),
+            ..pii
+        };
+        let decision = evaluate(&rules, &severe);
+        assert_eq!(decision.action, Some(ModerationAction::Reject));
+        assert_eq!(fired(&decision), ["publish", "hold_pii", "reject_severe"]);
+    }
+
+    #[test]
+    fn not_and_any_nest() {
+        // Fires unless the complaint has personal data or images
+        let rules = [rule(
+            "plain",
+            vec![RuleCondition::Not {
+                condition: Box::new(RuleCondition::Any {
+                    conditions: vec![RuleCondition::ContainsPii, RuleCondition::HasImages],
+                }),
+            }],
+            ModerationAction::Publish,
+            None,
+        )];
+
+        assert_eq!(
+            evaluate(&rules, &facts()).action,
+            Some(ModerationAction::Publish)
+        );
+        for facts in [
+            ComplaintFacts {
+                contains_pii: true,
+                ..facts()
+            },
+            ComplaintFacts {
+                image_count: 2,
+                ..facts()
+            },
+        ] {
+            assert_eq!(evaluate(&rules, &facts).action, None);
+        }
+        assert_eq!(
+            evaluate(
+                &[rule(
+                    "none",
+                    vec![RuleCondition::Any { conditions: vec![] }],
+                    ModerationAction::Hold,
+                    None
+                )],
+                &facts()
+            )
+            .action,
+            None
+        );
+    }
+
+    #[test]
+    fn reason_comes_from_a_rule_with_the_winning_action() {
+        let rules = [
+            rule(
+                "publish",
+                vec![],
+                ModerationAction::Publish,
+                Some("Looks fine"),
+            ),
+            rule("reject_silently", vec![], ModerationAction::Reject, None),
+            rule(
+                "reject_short",
+                vec![RuleCondition::DescriptionShorterThan { length: 20 }],
+                ModerationAction::Reject,
+                Some("Descriptions must be at least 20 characters long"),
+            ),
+        ];
+
+        let short = ComplaintFacts {
+            description_length: 5,
+            ..facts()
+        };
+        assert_eq!(
+            evaluate(&rules, &short).reason(),
+            Some("Descriptions must be at least 20 characters long")
+        );
+        // The publishing rule's reason is not the one given for a rejection
+        assert_eq!(evaluate(&rules, &facts()).reason(), None);
+    }
+}
@@ -1,25 +1,52 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::{
     error::ApiError,
-    utils::database::{PaginatedRecord, Pagination},
+    utils::database::{CursorPage, CursorPagination, Filter, PaginatedRecord, Pagination, Sort},
 };
 
-use super::{Complaint, ComplaintImage, Driver, DriverImage, Location};
+use super::{
+    BucketObject, Complaint, ComplaintFeedItem, ComplaintImage, ComplaintImageRedaction,
+    ComplaintSortField, Driver, DriverImage, DriverSortField, DuplicateImage, IdempotencyKey,
+    IdempotencyRecord, Location, ModerationRule, ObjectMetadata, PendingImage, PlateMatch,
+    PlateMatchMode, PresignedPost, ProcessedImageObjects, RateLimitHit, SharedImageComplaint,
+    SimilarComplaint, UploadPolicy,
+};
 
 #[async_trait]
 pub trait DBRepository: Send + Sync {
+    /// Start a unit of work, whose writes are applied together or not at all
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork>, ApiError>;
+
     // Driver operations
     async fn create_driver(&self, driver: &Driver) -> Result<Driver, ApiError>;
     async fn get_driver_by_id(&self, id: i32) -> Result<Driver, ApiError>;
-    async fn get_driver_by_license_plate(&self, license_plate: &str) -> Result<Driver, ApiError>;
+    /// The driver whose plate matches best, closest first in fuzzy mode
+    async fn get_driver_by_license_plate(
+        &self,
+        license_plate: &str,
+        mode: PlateMatchMode,
+    ) -> Result<Driver, ApiError>;
     async fn update_driver(&self, driver: &Driver) -> Result<Driver, ApiError>;
     async fn delete_driver(&self, id: i32) -> Result<(), ApiError>;
+    /// Drivers whose name or plate contains `query`, plus in fuzzy mode the
+    /// drivers whose plate looks like a misreading of it
     async fn search_drivers(
         &self,
         query: &str,
+        mode: PlateMatchMode,
+        filter: &Filter,
+        sort: &Sort<DriverSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Driver>, ApiError>;
+    async fn search_drivers_by_license_plate(
+        &self,
+        license_plate: &str,
+        mode: PlateMatchMode,
+        pagination: &Pagination,
+    ) -> Result<PaginatedRecord<PlateMatch>, ApiError>;
 
     // Driver Image operations
     async fn add_driver_image(&self, driver_image: &DriverImage) -> Result<DriverImage, ApiError>;
@@ -28,8 +55,41 @@ pub trait DBRepository: Send + Sync {
         driver_id: i32,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<DriverImage>, ApiError>;
+    /// First `per_driver` images of each driver, keyed by driver id, in a single query
+    async fn get_driver_images_for_drivers(
+        &self,
+        driver_ids: &[i32],
+        per_driver: u32,
+    ) -> Result<HashMap<i32, Vec<DriverImage>>, ApiError>;
     async fn delete_driver_image(&self, id: i32) -> Result<(), ApiError>;
 
+    // Image upload operations
+    /// Remember a key handed out for upload so it can be attached later
+    async fn record_image_upload(&self, image_key: &str) -> Result<(), ApiError>;
+    // Image processing operations
+    /// Claim up to `limit` images waiting for processing, including ones whose
+    /// processing was started long ago and never finished. Images that were
+    /// already claimed too many times are marked failed instead.
+    async fn claim_pending_images(&self, limit: u32) -> Result<Vec<PendingImage>, ApiError>;
+    async fn complete_image_processing(
+        &self,
+        image: &PendingImage,
+        objects: &ProcessedImageObjects,
+    ) -> Result<(), ApiError>;
+    async fn fail_image_processing(
+        &self,
+        image: &PendingImage,
+        error: &str,
+    ) -> Result<(), ApiError>;
+
+    /// Subset of `image_keys` referenced by a driver or complaint image
+    async fn get_referenced_image_keys(
+        &self,
+        image_keys: &[String],
+    ) -> Result<Vec<String>, ApiError>;
+    /// Forget issued uploads that were never attached, once their objects are gone
+    async fn delete_unattached_image_uploads(&self, image_keys: &[String]) -> Result<(), ApiError>;
+
     // Complaint operations
     async fn create_complaint(&self, complaint: &Complaint) -> Result<Complaint, ApiError>;
     async fn get_complaint_by_id(&self, id: i32) -> Result<Complaint, ApiError>;
@@ -38,8 +98,30 @@ pub trait DBRepository: Send + Sync {
     async fn get_complaints_for_driver(
         &self,
         driver_id: i32,
+        filter: &Filter,
+        sort: &Sort<ComplaintSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Complaint>, ApiError>;
+    /// The same page of complaints for each driver, keyed by driver id, in a single query
+    async fn get_complaints_for_drivers(
+        &self,
+        driver_ids: &[i32],
+        filter: &Filter,
+        sort: &Sort<ComplaintSortField>,
+        pagination: &Pagination,
+    ) -> Result<HashMap<i32, PaginatedRecord<Complaint>>, ApiError>;
+    /// Published complaints across all drivers, newest first
+    async fn get_published_complaints(
+        &self,
+        filter: &Filter,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<ComplaintFeedItem>, ApiError>;
+    async fn get_complaints_for_driver_by_cursor(
+        &self,
+        driver_id: i32,
+        filter: &Filter,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<Complaint>, ApiError>;
 
     // Complaint Image operations
     async fn add_complaint_image(
@@ -51,6 +133,13 @@ pub trait DBRepository: Send + Sync {
         complaint_id: i32,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<ComplaintImage>, ApiError>;
+    async fn get_complaint_image_by_id(&self, id: i32) -> Result<ComplaintImage, ApiError>;
+    /// Point a complaint image to its redacted copy, remembering the private original
+    async fn save_complaint_image_redaction(
+        &self,
+        id: i32,
+        redaction: &ComplaintImageRedaction,
+    ) -> Result<ComplaintImage, ApiError>;
     async fn delete_complaint_image(&self, id: i32) -> Result<(), ApiError>;
 
     // Location operations
@@ -70,13 +159,148 @@ pub trait DBRepository: Send + Sync {
         name: &str,
         license_plate: &str,
     ) -> Result<Driver, ApiError>;
+
+    // Moderation operations
+    async fn is_moderator(&self, user_id: &str) -> Result<bool, ApiError>;
+    async fn is_verified_reporter(&self, user_id: &str) -> Result<bool, ApiError>;
+    /// Enabled moderation rules. Rules whose conditions cannot be read are left out.
+    async fn get_moderation_rules(&self) -> Result<Vec<ModerationRule>, ApiError>;
+    async fn get_pending_complaints(
+        &self,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<Complaint>, ApiError>;
+    /// Images of other drivers or reporters whose perceptual hash is within
+    /// `max_distance` of an image of each complaint. Only images uploaded
+    /// within `window` are compared, except for exact copies.
+    async fn find_duplicate_images(
+        &self,
+        complaint_ids: &[i32],
+        max_distance: u32,
+        window: Duration,
+    ) -> Result<HashMap<i32, Vec<DuplicateImage>>, ApiError>;
+    /// Complaints with an image whose perceptual hash is within `max_distance`
+    /// of the complaint image `image_id`, closest first
+    async fn get_complaints_sharing_image(
+        &self,
+        image_id: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SharedImageComplaint>, ApiError>;
+
+    // Idempotency operations
+    /// The request `submitter` completed with an unexpired idempotency key,
+    /// without claiming it
+    async fn get_idempotency_record(
+        &self,
+        submitter: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<IdempotencyRecord>, ApiError>;
+}
+
+/// Writes made in a single database transaction. Nothing is persisted until
+/// `commit`, and dropping the unit of work rolls everything back.
+#[async_trait]
+pub trait UnitOfWork: Send {
+    /// Get the driver with this name and license plate, creating it if needed.
+    /// Concurrent calls for the same driver all get the same row.
+    async fn upsert_driver(&mut self, driver: &Driver) -> Result<Driver, ApiError>;
+    async fn add_driver_image(
+        &mut self,
+        driver_image: &DriverImage,
+    ) -> Result<DriverImage, ApiError>;
+    async fn create_complaint(&mut self, complaint: &Complaint) -> Result<Complaint, ApiError>;
+    /// The complaint against `driver_id` created within `window` whose
+    /// description is the most similar to `description`, if any is at least
+    /// `min_similarity` similar. Complaints of the same submitter come first.
+    async fn find_similar_complaint(
+        &mut self,
+        driver_id: i32,
+        description: &str,
+        submitter_hash: &str,
+        window: Duration,
+        min_similarity: f32,
+    ) -> Result<Option<SimilarComplaint>, ApiError>;
+    async fn add_complaint_image(
+        &mut self,
+        complaint_image: &ComplaintImage,
+    ) -> Result<ComplaintImage, ApiError>;
+    /// Mark uploads as attached if all of them were issued and none is attached yet.
+    /// Returns the keys that were available, so callers can tell which one was not.
+    async fn claim_image_uploads(&mut self, image_keys: &[String])
+        -> Result<Vec<String>, ApiError>;
+
+    /// Hold an idempotency key until the unit of work ends. Returns the earlier
+    /// request made with it, if any; requests racing for the same key wait for
+    /// the first one to commit or roll back.
+    async fn claim_idempotency_key(
+        &mut self,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<IdempotencyRecord>, ApiError>;
+    /// Save the response to replay for a claimed idempotency key
+    async fn save_idempotent_response(
+        &mut self,
+        idempotency_key: &IdempotencyKey,
+        response: &str,
+    ) -> Result<(), ApiError>;
+
+    async fn commit(self: Box<Self>) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count a hit against `key` in the current fixed window of length `window`
+    async fn hit(&self, key: &str, window: Duration) -> Result<RateLimitHit, ApiError>;
+    /// Forget counters whose window is over, returning how many there were
+    async fn prune(&self) -> Result<u64, ApiError>;
 }
 
+#[async_trait]
+pub trait CaptchaPort: Send + Sync {
+    /// Whether `token`, solved by the client, proves a human sent the request.
+    /// Each token is only accepted once.
+    async fn verify(&self, token: &str) -> Result<bool, ApiError>;
+}
+
+/// Objects under this prefix are never handed out through signed URLs, except
+/// to moderators
+pub const PRIVATE_KEY_PREFIX: &str = "private/";
+
 #[async_trait]
 pub trait BucketPort: Send + Sync {
-    /// Generate a presigned URL for uploading an image to the bucket
-    /// Returns the presigned URL and the public URL where the image will be accessible after upload
-    async fn generate_upload_url(&self, file_name: &str) -> Result<(String, String), ApiError>;
+    /// Generate a presigned form upload to the bucket, restricted by `policy`
+    async fn generate_upload_url(
+        &self,
+        file_name: &str,
+        policy: &UploadPolicy,
+    ) -> Result<PresignedPost, ApiError>;
+
+    async fn get_object(&self, file_name: &str) -> Result<Vec<u8>, ApiError>;
+
+    async fn put_object(
+        &self,
+        file_name: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), ApiError>;
+
+    /// Every object whose key starts with `prefix`
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, ApiError>;
+
+    /// Unsigned URL of an object. Objects are private, so this only identifies
+    /// them; clients are handed `signed_url`s to read them.
+    fn public_url(&self, file_name: &str) -> String;
+
+    /// Short-lived URL granting read access to a private object. URLs signed
+    /// at the same `signed_at` are identical, so responses embedding them can
+    /// still be cached.
+    async fn signed_url(
+        &self,
+        file_name: &str,
+        signed_at: chrono::DateTime<chrono::Utc>,
+        expires_in: Duration,
+    ) -> Result<String, ApiError>;
+
+    /// Content type, size and checksum of an uploaded object
+    async fn object_metadata(&self, file_name: &str) -> Result<ObjectMetadata, ApiError>;
 
     /// Delete an image from the bucket
     async fn delete_image(&self, file_name: &str) -> Result<(), ApiError>;
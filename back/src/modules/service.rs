@@ -1,18 +1,83 @@
 use super::{
-    port::{BucketPort, DBRepository},
-    Complaint, ComplaintImage, ComplaintWithImages, Driver, DriverImage, DriverWithDetails,
-    DriverWithImages, NewComplaint,
+    moderation_rules,
+    port::{BucketPort, CaptchaPort, DBRepository, UnitOfWork, PRIVATE_KEY_PREFIX},
+    BucketObject, CaptchaEndpoint, Complaint, ComplaintFacts, ComplaintFeedItem, ComplaintFilters,
+    ComplaintImage, ComplaintImageRedaction, ComplaintSortField, ComplaintWithImages, Driver,
+    DriverImage, DriverSortField, DriverWithDetails, DriverWithImages, IdempotencyKey,
+    IdempotencyRecord, Idempotent, ImageProcessingStatus, ModerationAction, ModerationQueueItem,
+    NewComplaint, ObjectMetadata, OrphanedUploadsReport, PendingImage, PlateMatch, PlateMatchMode,
+    PresignedUpload, ProcessedImageObjects, RedactionRegion, SharedImageComplaint, UploadPolicy,
+    UploadPurpose,
 };
 use crate::{
     error::ApiError,
-    utils::database::{PaginatedRecord, Pagination},
+    utils::{
+        database::{CursorPage, CursorPagination, Filter, PaginatedRecord, Pagination, Sort},
+        image_processing, pii,
+        profanity::ProfanityFilter,
+    },
 };
-use futures::{future, TryFutureExt};
+use futures::future;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Proof that a request passed the CAPTCHA check of its endpoint, obtained from
+/// `Service::verify_captcha`
+pub struct CaptchaPass(());
+
+/// Most evidence images a single complaint can carry
+pub const MAX_COMPLAINT_IMAGES: usize = 10;
+
+/// Complaints against the same driver submitted this recently are compared
+/// with a new one to catch resubmissions
+const DUPLICATE_COMPLAINT_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Trigram similarity from which a description is linked to the earlier one
+/// for moderators to review
+const DUPLICATE_COMPLAINT_SIMILARITY: f32 = 0.6;
+/// Near-identical resubmissions by the same submitter, in the same category and
+/// this soon, are refused. Other victims' complaints are only ever linked.
+const REPEATED_COMPLAINT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+const REPEATED_COMPLAINT_SIMILARITY: f32 = 0.9;
+
+/// Upload purposes whose objects are referenced from the image tables. Claim
+/// documents are not tracked anywhere yet, so they are never collected.
+const COLLECTED_UPLOAD_PURPOSES: [UploadPurpose; 2] =
+    [UploadPurpose::DriverPhoto, UploadPurpose::ComplaintEvidence];
+/// Keys checked against the database per query when collecting uploads
+const UPLOAD_GC_BATCH: usize = 1000;
+
+/// How long signed image URLs stay valid
+const SIGNED_URL_TTL: Duration = Duration::from_secs(15 * 60);
+/// Image URLs are signed at the start of fixed windows, so the same URL is
+/// handed out for a whole window and responses embedding it stay cacheable.
+/// A URL therefore remains valid for at least `TTL - WINDOW` once handed out.
+const SIGNED_URL_WINDOW_SECS: i64 = 5 * 60;
+
+/// Perceptual hashes at most this many bits apart are taken for the same photo
+pub const DUPLICATE_IMAGE_MAX_DISTANCE: u32 = 6;
+/// Beyond this, unrelated photos start matching
+const MAX_DUPLICATE_IMAGE_DISTANCE: u32 = 16;
+/// How far back the moderation queue looks for near-duplicates of an image.
+/// Exact copies are found whatever their age.
+const DUPLICATE_IMAGE_WINDOW: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Images processed per round trip to the database
+const IMAGE_PROCESSING_BATCH: u32 = 10;
+/// How often the image pipeline looks for work when nobody wakes it up
+const IMAGE_PROCESSING_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct Service {
     db_repo: Arc<dyn DBRepository>,
     bucket_repo: Arc<dyn BucketPort>,
+    /// Wakes the image pipeline when new images are attached
+    pending_images: Notify,
+    captcha: Option<Arc<dyn CaptchaPort>>,
+    captcha_endpoints: HashSet<CaptchaEndpoint>,
+    profanity_filter: ProfanityFilter,
 }
 
 impl Service {
@@ -20,24 +85,224 @@ impl Service {
         Self {
             db_repo,
             bucket_repo,
+            pending_images: Notify::new(),
+            captcha: None,
+            captcha_endpoints: HashSet::new(),
+            profanity_filter: ProfanityFilter::default(),
+        }
+    }
+
+    /// Filter profanity with `profanity_filter` instead of the default word lists
+    pub fn with_profanity_filter(mut self, profanity_filter: ProfanityFilter) -> Self {
+        self.profanity_filter = profanity_filter;
+        self
+    }
+
+    /// Require a CAPTCHA token verified by `captcha` on `endpoints`
+    pub fn with_captcha(
+        mut self,
+        captcha: Arc<dyn CaptchaPort>,
+        endpoints: impl IntoIterator<Item = CaptchaEndpoint>,
+    ) -> Self {
+        self.captcha = Some(captcha);
+        self.captcha_endpoints = endpoints.into_iter().collect();
+        self
+    }
+
+    /// Checks the CAPTCHA token of a request to `endpoint`, if that endpoint
+    /// requires one. Tokens are single use, so retries of a request the same
+    /// submitter already completed under `idempotency_key` pass without one.
+    pub async fn verify_captcha(
+        &self,
+        endpoint: CaptchaEndpoint,
+        token: Option<&str>,
+        idempotency_key: Option<&IdempotencyKey>,
+    ) -> Result<CaptchaPass, ApiError> {
+        let Some(captcha) = &self.captcha else {
+            return Ok(CaptchaPass(()));
+        };
+        if !self.captcha_endpoints.contains(&endpoint) {
+            return Ok(CaptchaPass(()));
+        }
+        if let Some(idempotency_key) = idempotency_key {
+            let completed = self
+                .db_repo
+                .get_idempotency_record(&idempotency_key.submitter, &idempotency_key.key)
+                .await?
+                .is_some_and(|previous| {
+                    previous.response.is_some()
+                        && previous.request_hash == idempotency_key.request_hash
+                });
+            if completed {
+                return Ok(CaptchaPass(()));
+            }
+        }
+
+        let token = token
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| ApiError::BadRequest("A CAPTCHA token is required".to_string()))?;
+        if !captcha.verify(token).await? {
+            return Err(ApiError::Forbidden(
+                "CAPTCHA verification failed".to_string(),
+            ));
         }
+
+        Ok(CaptchaPass(()))
     }
 
+    /// Creates a complaint with its driver and images. With an idempotency key,
+    /// a retry of the same request replays the complaint created the first time.
     pub async fn create_complaint(
         &self,
         new_complaint: NewComplaint,
-    ) -> Result<Complaint, ApiError> {
-        // Check if driver exists or create a new one
-        let driver = self.get_or_create_driver(&new_complaint).await?;
+        idempotency_key: Option<&IdempotencyKey>,
+        _captcha: CaptchaPass,
+    ) -> Result<Idempotent<Complaint>, ApiError> {
+        // Replays are answered before anything else, their uploads may be gone
+        // from the bucket since they were processed
+        if let Some(idempotency_key) = idempotency_key {
+            if let Some(previous) = self
+                .db_repo
+                .get_idempotency_record(&idempotency_key.submitter, &idempotency_key.key)
+                .await?
+            {
+                return Self::replay(idempotency_key, previous).map(Idempotent::Replayed);
+            }
+        }
+
+        // Verify images before writing anything
+        let driver_image_key = new_complaint
+            .driver_image
+            .as_deref()
+            .map(|image| self.object_key(image, UploadPurpose::DriverPhoto))
+            .transpose()?;
+        let complaint_image_keys = new_complaint
+            .complaint_images
+            .iter()
+            .flatten()
+            .map(|image| self.object_key(image, UploadPurpose::ComplaintEvidence))
+            .collect::<Result<Vec<_>, _>>()?;
+        if complaint_image_keys.len() > MAX_COMPLAINT_IMAGES {
+            return Err(ApiError::BadRequest(format!(
+                "A complaint can have at most {} images",
+                MAX_COMPLAINT_IMAGES
+            )));
+        }
+
+        let uploads: Vec<(&String, UploadPurpose)> = driver_image_key
+            .iter()
+            .map(|key| (key, UploadPurpose::DriverPhoto))
+            .chain(
+                complaint_image_keys
+                    .iter()
+                    .map(|key| (key, UploadPurpose::ComplaintEvidence)),
+            )
+            .collect();
+        let image_keys: Vec<String> = uploads.iter().map(|(key, _)| (*key).clone()).collect();
+        let mut metadata = future::try_join_all(
+            uploads
+                .iter()
+                .map(|(key, purpose)| self.verify_upload(key, *purpose)),
+        )
+        .await?
+        .into_iter();
+
+        // Personal data and mild profanity are masked in the stored description,
+        // the original is kept for moderators. Mild profanity is masked in
+        // driver names too; masking is deterministic, so the same name still
+        // matches the same driver.
+        let country = match self
+            .db_repo
+            .get_location_by_id(new_complaint.location_id)
+            .await
+        {
+            Ok(location) => Some(location.country),
+            Err(ApiError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let description_length = new_complaint.description.trim().chars().count();
+        let pii = pii::mask_pii(&new_complaint.description, country);
+        let profanity = self.profanity_filter.scan(&pii.masked, country);
+        let driver_name = self
+            .profanity_filter
+            .scan(&new_complaint.taxi_driver_name, country);
+        let profanity_severity = profanity.severity.max(driver_name.severity);
+        let (description, original_description) = if profanity.masked == new_complaint.description {
+            (new_complaint.description, None)
+        } else {
+            (profanity.masked, Some(new_complaint.description))
+        };
+        let reporter_verified = match &new_complaint.reporter_id {
+            Some(reporter_id) => self.db_repo.is_verified_reporter(reporter_id).await?,
+            None => false,
+        };
+        let rules = self.db_repo.get_moderation_rules().await?;
 
-        // Add driver image if provided
-        if let Some(driver_image_url) = new_complaint.driver_image {
-            let driver_image = DriverImage {
+        // Everything is written in one transaction, so a failure midway leaves
+        // neither a half-created complaint nor claimed uploads or keys behind.
+        // It only starts now, so the idempotency key is not held while waiting
+        // on the CAPTCHA provider or the bucket.
+        let mut unit_of_work = self.db_repo.begin().await?;
+        if let Some(idempotency_key) = idempotency_key {
+            // A concurrent request with the same key may have completed since
+            if let Some(previous) = unit_of_work.claim_idempotency_key(idempotency_key).await? {
+                return Self::replay(idempotency_key, previous).map(Idempotent::Replayed);
+            }
+        }
+        Self::claim_uploads(unit_of_work.as_mut(), &image_keys).await?;
+
+        let driver = unit_of_work
+            .upsert_driver(&Driver {
                 id: 0, // This will be set by the database
-                driver_id: driver.id,
-                image_url: driver_image_url,
-            };
-            self.db_repo.add_driver_image(&driver_image).await?;
+                name: driver_name.masked,
+                license_plate: new_complaint.taxi_license_plate,
+            })
+            .await?;
+
+        // Refuse resubmissions of a recent complaint, and link the ones that
+        // only resemble it for review
+        let submitter_hash = hex::encode(Sha256::digest(new_complaint.submitter.as_bytes()));
+        let similar = unit_of_work
+            .find_similar_complaint(
+                driver.id,
+                &description,
+                &submitter_hash,
+                DUPLICATE_COMPLAINT_WINDOW,
+                DUPLICATE_COMPLAINT_SIMILARITY,
+            )
+            .await?;
+        if let Some(similar) = &similar {
+            let repeated = similar.same_submitter
+                && similar.similarity >= REPEATED_COMPLAINT_SIMILARITY
+                && similar.category == new_complaint.category
+                && chrono::Utc::now() - similar.created_at
+                    < chrono::Duration::from_std(REPEATED_COMPLAINT_WINDOW).unwrap_or_default();
+            if repeated {
+                return Err(ApiError::DuplicateComplaint(similar.id));
+            }
+        }
+
+        // Moderation rules decide whether the complaint is refused, published
+        // right away or left for a moderator
+        let decision = moderation_rules::evaluate(
+            &rules,
+            &ComplaintFacts {
+                description_length,
+                category: new_complaint.category,
+                contains_pii: !pii.kinds.is_empty(),
+                profanity_severity,
+                reporter_verified,
+                possible_duplicate: similar.is_some(),
+                image_count: complaint_image_keys.len(),
+            },
+        );
+        if decision.action == Some(ModerationAction::Reject) {
+            return Err(ApiError::BadRequest(
+                decision
+                    .reason()
+                    .unwrap_or("The complaint was rejected by moderation rules")
+                    .to_string(),
+            ));
         }
 
         // Create the complaint
@@ -46,49 +311,341 @@ impl Service {
             driver_id: driver.id,
             location_id: new_complaint.location_id,
             taxi_application: new_complaint.taxi_application,
-            description: new_complaint.description,
+            description,
+            category: new_complaint.category,
+            incident_at: new_complaint.incident_at,
             created_at: chrono::Utc::now(),
+            // Published right away even while its images are being processed:
+            // images are only listed and signed once they are ready
+            published: decision.action == Some(ModerationAction::Publish),
+            duplicate_of_id: similar.map(|similar| similar.id),
+            original_description,
+            pii_kinds: pii.kinds,
+            profanity_severity,
+            reporter_id: new_complaint.reporter_id,
+            submitter_hash: Some(submitter_hash),
+            fired_rules: decision
+                .fired
+                .iter()
+                .map(|rule| rule.name.clone())
+                .collect(),
         };
-        let created_complaint = self.db_repo.create_complaint(&complaint).await?;
+        let created_complaint = unit_of_work.create_complaint(&complaint).await?;
+
+        // Add driver image if provided, shown publicly along with the complaint
+        if let Some(image_key) = driver_image_key {
+            let metadata = metadata.next().unwrap_or_default();
+            let driver_image =
+                DriverImage::new(driver.id, created_complaint.id, &image_key, metadata);
+            unit_of_work.add_driver_image(&driver_image).await?;
+        }
 
         // Add complaint images if provided
-        if let Some(complaint_image_urls) = new_complaint.complaint_images {
-            for image_url in complaint_image_urls {
-                let complaint_image = ComplaintImage {
-                    id: 0, // This will be set by the database
-                    complaint_id: created_complaint.id,
-                    image_url,
-                };
-                self.db_repo.add_complaint_image(&complaint_image).await?;
+        for image_key in complaint_image_keys {
+            let metadata = metadata.next().unwrap_or_default();
+            let complaint_image = ComplaintImage::new(created_complaint.id, &image_key, metadata);
+            unit_of_work.add_complaint_image(&complaint_image).await?;
+        }
+
+        if let Some(idempotency_key) = idempotency_key {
+            unit_of_work
+                .save_idempotent_response(
+                    idempotency_key,
+                    &serde_json::to_string(&created_complaint)?,
+                )
+                .await?;
+        }
+
+        unit_of_work.commit().await?;
+        if !image_keys.is_empty() {
+            self.pending_images.notify_one();
+        }
+
+        Ok(Idempotent::Fresh(created_complaint))
+    }
+
+    /// The response to an earlier request made with the same idempotency key
+    fn replay<T: DeserializeOwned>(
+        idempotency_key: &IdempotencyKey,
+        previous: IdempotencyRecord,
+    ) -> Result<T, ApiError> {
+        if previous.request_hash != idempotency_key.request_hash {
+            return Err(ApiError::Conflict(format!(
+                "Idempotency key '{}' was already used for a different request",
+                idempotency_key.key
+            )));
+        }
+
+        let response = previous.response.ok_or_else(|| {
+            ApiError::Conflict(format!(
+                "A request with idempotency key '{}' is still being processed",
+                idempotency_key.key
+            ))
+        })?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    /// Key of an image uploaded for `purpose`. Clients send the key returned with
+    /// the upload URL, but older clients send the public URL, which is accepted
+    /// as long as it points into our bucket.
+    fn object_key(&self, image: &str, purpose: UploadPurpose) -> Result<String, ApiError> {
+        let key = if image.contains("://") {
+            let public_base = self.bucket_repo.public_url("");
+            match image.strip_prefix(&public_base) {
+                Some(key) if !key.is_empty() => key,
+                _ => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Image {} is not stored in our bucket",
+                        image
+                    )))
+                }
+            }
+        } else {
+            image
+        };
+
+        if UploadPurpose::from_key(key) != Some(purpose) {
+            return Err(ApiError::BadRequest(format!(
+                "Image {} was not uploaded as a {}",
+                image,
+                purpose.as_str()
+            )));
+        }
+
+        Ok(key.to_string())
+    }
+
+    /// Checks that an image was actually uploaded within the limits of its
+    /// purpose, returning what the bucket knows about it
+    async fn verify_upload(
+        &self,
+        key: &str,
+        purpose: UploadPurpose,
+    ) -> Result<ObjectMetadata, ApiError> {
+        let metadata = match self.bucket_repo.object_metadata(key).await {
+            Err(ApiError::NotFound(_)) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Image {} has not been uploaded",
+                    key
+                )))
+            }
+            result => result?,
+        };
+
+        if metadata
+            .size_bytes
+            .is_some_and(|size| size > purpose.max_size_bytes())
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Image {} is larger than {} bytes",
+                key,
+                purpose.max_size_bytes()
+            )));
+        }
+        if let Some(content_type) = &metadata.content_type {
+            if !purpose
+                .allowed_content_types()
+                .contains(&content_type.as_str())
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "Image {} has unsupported content type '{}'",
+                    key, content_type
+                )));
             }
         }
 
-        Ok(created_complaint)
+        Ok(metadata)
     }
 
-    async fn get_or_create_driver(&self, new_complaint: &NewComplaint) -> Result<Driver, ApiError> {
-        match self
-            .db_repo
-            .get_driver_by_name_and_license_plate(
-                &new_complaint.taxi_driver_name,
-                &new_complaint.taxi_license_plate,
-            )
+    /// Attaches each image at most once, and only if we handed out its key
+    async fn claim_uploads(
+        unit_of_work: &mut dyn UnitOfWork,
+        keys: &[String],
+    ) -> Result<(), ApiError> {
+        let mut unique_keys = HashSet::new();
+        if let Some(key) = keys.iter().find(|key| !unique_keys.insert(*key)) {
+            return Err(ApiError::BadRequest(format!(
+                "Image {} is attached more than once",
+                key
+            )));
+        }
+
+        let available: HashSet<String> = unit_of_work
+            .claim_image_uploads(keys)
+            .await?
+            .into_iter()
+            .collect();
+        match keys.iter().find(|key| !available.contains(*key)) {
+            Some(key) => Err(ApiError::BadRequest(format!(
+                "Image {} was not issued for upload or is already attached",
+                key
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Signs a read of a private object for the current window
+    async fn signed_url(&self, key: &str) -> Result<String, ApiError> {
+        let now = chrono::Utc::now().timestamp();
+        let signed_at =
+            chrono::DateTime::from_timestamp(now - now.rem_euclid(SIGNED_URL_WINDOW_SECS), 0)
+                .ok_or_else(|| ApiError::UnexpectedError("Invalid signing time".to_string()))?;
+
+        self.bucket_repo
+            .signed_url(key, signed_at, SIGNED_URL_TTL)
             .await
-        {
-            Ok(driver) => Ok(driver),
-            Err(ApiError::NotFound(_)) => {
-                // Driver not found, create a new one
-                let new_driver = Driver {
-                    id: 0, // This will be set by the database
-                    name: new_complaint.taxi_driver_name.clone(),
-                    license_plate: new_complaint.taxi_license_plate.clone(),
-                };
-                self.db_repo.create_driver(&new_driver).await
+    }
+
+    async fn signed_thumbnail_url(&self, key: Option<&str>) -> Result<Option<String>, ApiError> {
+        match key {
+            Some(key) => Ok(Some(self.signed_url(key).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Images still waiting for processing may carry EXIF metadata, and failed
+    /// ones may not be images at all, so only ready images get URLs. Like
+    /// complaint images, photos only get them once the complaint they came
+    /// with is published, unless a moderator is asking.
+    async fn resolve_driver_image_urls(
+        &self,
+        images: &mut [DriverImage],
+        is_moderator: bool,
+    ) -> Result<(), ApiError> {
+        for image in images {
+            if image.processing_status != ImageProcessingStatus::Ready
+                || !(image.published || is_moderator)
+            {
+                image.image_url = None;
+                image.thumbnail_url = None;
+                continue;
+            }
+            image.image_url = Some(self.signed_url(&image.image_key).await?);
+            image.thumbnail_url = self
+                .signed_thumbnail_url(image.thumbnail_key.as_deref())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Only call this for published complaints, or on behalf of a moderator.
+    /// Like driver images, only ready images get URLs.
+    async fn resolve_complaint_image_urls(
+        &self,
+        images: &mut [ComplaintImage],
+    ) -> Result<(), ApiError> {
+        for image in images {
+            if image.processing_status != ImageProcessingStatus::Ready {
+                image.image_url = None;
+                image.thumbnail_url = None;
+                continue;
+            }
+            image.image_url = Some(self.signed_url(&image.image_key).await?);
+            image.thumbnail_url = self
+                .signed_thumbnail_url(image.thumbnail_key.as_deref())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the image pipeline forever, processing images as they are attached
+    pub async fn run_image_pipeline(self: Arc<Self>) {
+        loop {
+            match self.process_pending_images().await {
+                Ok(0) => {}
+                Ok(processed) => log::info!("Processed {} images", processed),
+                Err(e) => log::error!("Image pipeline failed: {}", e),
+            }
+
+            // Either woken up by a new image or time to look for retries
+            let _ = tokio::time::timeout(IMAGE_PROCESSING_INTERVAL, self.pending_images.notified())
+                .await;
+        }
+    }
+
+    /// Processes queued images until none is left, returning how many were handled
+    pub async fn process_pending_images(&self) -> Result<usize, ApiError> {
+        let mut handled = 0;
+        loop {
+            let images = self
+                .db_repo
+                .claim_pending_images(IMAGE_PROCESSING_BATCH)
+                .await?;
+            if images.is_empty() {
+                return Ok(handled);
+            }
+
+            for image in &images {
+                match self.process_image(image).await {
+                    Ok(objects) => {
+                        self.db_repo
+                            .complete_image_processing(image, &objects)
+                            .await?;
+                        // The original may carry EXIF metadata, only the processed copy is kept
+                        if let Err(e) = self.bucket_repo.delete_image(&image.image_key).await {
+                            log::warn!("Failed to delete original {}: {}", image.image_key, e);
+                        }
+                    }
+                    // Not an image, or gone from the bucket: retrying will not help
+                    Err(e @ (ApiError::BadRequest(_) | ApiError::NotFound(_))) => {
+                        log::warn!("Rejected image {}: {}", image.image_key, e);
+                        self.db_repo
+                            .fail_image_processing(image, &e.to_string())
+                            .await?;
+                    }
+                    // Left claimed, so it is picked up again once the claim times out,
+                    // until it runs out of attempts
+                    Err(e) => log::error!("Failed to process image {}: {}", image.image_key, e),
+                }
             }
-            Err(e) => Err(e),
+            handled += images.len();
         }
     }
 
+    async fn process_image(&self, image: &PendingImage) -> Result<ProcessedImageObjects, ApiError> {
+        let original = self.bucket_repo.get_object(&image.image_key).await?;
+        let processed =
+            tokio::task::spawn_blocking(move || image_processing::process_image(&original))
+                .await
+                .map_err(|e| ApiError::UnexpectedError(e.to_string()))??;
+
+        self.store_processed_image(&image.image_key, processed)
+            .await
+    }
+
+    /// Uploads a processed image and its thumbnail next to `base_key`
+    async fn store_processed_image(
+        &self,
+        base_key: &str,
+        processed: image_processing::ProcessedImage,
+    ) -> Result<ProcessedImageObjects, ApiError> {
+        let image_key = format!("{}.webp", base_key);
+        let thumbnail_key = format!("{}.thumb.webp", base_key);
+        let metadata = ObjectMetadata {
+            content_type: Some("image/webp".to_string()),
+            size_bytes: Some(processed.image.len() as i64),
+            sha256: Some(hex::encode(Sha256::digest(&processed.image))),
+        };
+        future::try_join(
+            self.bucket_repo
+                .put_object(&image_key, processed.image, "image/webp"),
+            self.bucket_repo
+                .put_object(&thumbnail_key, processed.thumbnail, "image/webp"),
+        )
+        .await?;
+
+        Ok(ProcessedImageObjects {
+            image_key,
+            thumbnail_key,
+            width: processed.width as i32,
+            height: processed.height as i32,
+            metadata,
+            perceptual_hash: processed.perceptual_hash as i64,
+        })
+    }
+
     pub async fn get_driver(&self, driver_id: i32) -> Result<Driver, ApiError> {
         self.db_repo.get_driver_by_id(driver_id).await
     }
@@ -96,6 +653,8 @@ impl Service {
     pub async fn get_driver_complaints(
         &self,
         driver_id: i32,
+        filters: &ComplaintFilters,
+        sort: &Sort<ComplaintSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Complaint>, ApiError> {
         // First, check if the driver exists
@@ -103,7 +662,37 @@ impl Service {
 
         // If the driver exists, get their complaints
         self.db_repo
-            .get_complaints_for_driver(driver_id, pagination)
+            .get_complaints_for_driver(driver_id, &filters.to_filter(), sort, pagination)
+            .await
+    }
+
+    pub async fn get_complaint_feed(
+        &self,
+        filters: &ComplaintFilters,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<ComplaintFeedItem>, ApiError> {
+        let mut feed = self
+            .db_repo
+            .get_published_complaints(&filters.to_filter(), pagination)
+            .await?;
+        for item in &mut feed.items {
+            self.resolve_complaint_image_urls(&mut item.complaint.images)
+                .await?;
+        }
+
+        Ok(feed)
+    }
+
+    pub async fn get_driver_complaints_by_cursor(
+        &self,
+        driver_id: i32,
+        filters: &ComplaintFilters,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<Complaint>, ApiError> {
+        self.db_repo.get_driver_by_id(driver_id).await?;
+
+        self.db_repo
+            .get_complaints_for_driver_by_cursor(driver_id, &filters.to_filter(), pagination)
             .await
     }
 
@@ -111,13 +700,14 @@ impl Service {
         &self,
         driver_id: i32,
         pagination: &Pagination,
+        is_moderator: bool,
     ) -> Result<DriverWithDetails, ApiError> {
         let driver = self.db_repo.get_driver_by_id(driver_id).await?;
         let complaints = self
             .db_repo
-            .get_complaints_for_driver(driver_id, pagination)
+            .get_complaints_for_driver(driver_id, &Filter::new(), &Sort::default(), pagination)
             .await?;
-        let driver_images = self
+        let mut driver_images = self
             .db_repo
             .get_driver_images(
                 driver_id,
@@ -127,6 +717,8 @@ impl Service {
                 },
             )
             .await?;
+        self.resolve_driver_image_urls(&mut driver_images.items, is_moderator)
+            .await?;
 
         Ok(DriverWithDetails {
             driver,
@@ -138,48 +730,80 @@ impl Service {
     pub async fn search_drivers(
         &self,
         query: &str,
+        mode: PlateMatchMode,
+        filters: &ComplaintFilters,
+        sort: &Sort<DriverSortField>,
         pagination: &Pagination,
     ) -> Result<PaginatedRecord<Driver>, ApiError> {
-        self.db_repo.search_drivers(query, pagination).await
+        self.db_repo
+            .search_drivers(query, mode, &filters.to_filter(), sort, pagination)
+            .await
+    }
+
+    pub async fn search_drivers_by_license_plate(
+        &self,
+        license_plate: &str,
+        mode: PlateMatchMode,
+        pagination: &Pagination,
+    ) -> Result<PaginatedRecord<PlateMatch>, ApiError> {
+        if license_plate.trim().is_empty() {
+            return Err(ApiError::BadRequest(
+                "License plate must not be empty".to_string(),
+            ));
+        }
+
+        self.db_repo
+            .search_drivers_by_license_plate(license_plate, mode, pagination)
+            .await
     }
 
     pub async fn search_drivers_with_images(
         &self,
         query: &str,
+        mode: PlateMatchMode,
+        filters: &ComplaintFilters,
+        sort: &Sort<DriverSortField>,
         pagination: &Pagination,
+        is_moderator: bool,
     ) -> Result<PaginatedRecord<DriverWithImages>, ApiError> {
-        let drivers = self.db_repo.search_drivers(query, pagination).await?;
-
-        let drivers_with_images: Vec<DriverWithImages> =
-            future::try_join_all(drivers.items.into_iter().map(|driver| {
-                self.db_repo
-                    .get_driver_images(
-                        driver.id,
-                        &Pagination {
-                            page: 1,
-                            per_page: 5,
-                        },
-                    )
-                    .map_ok(move |images| DriverWithImages {
-                        driver,
-                        images: images.items,
-                    })
-                    .map_err(ApiError::from)
-            }))
+        let drivers = self
+            .db_repo
+            .search_drivers(query, mode, &filters.to_filter(), sort, pagination)
             .await?;
 
+        let driver_ids: Vec<i32> = drivers.items.iter().map(|driver| driver.id).collect();
+        let mut images = self
+            .db_repo
+            .get_driver_images_for_drivers(&driver_ids, 5)
+            .await?;
+        for driver_images in images.values_mut() {
+            self.resolve_driver_image_urls(driver_images, is_moderator)
+                .await?;
+        }
+
+        let drivers_with_images: Vec<DriverWithImages> = drivers
+            .items
+            .into_iter()
+            .map(|driver| DriverWithImages {
+                images: images.remove(&driver.id).unwrap_or_default(),
+                driver,
+            })
+            .collect();
+
         Ok(PaginatedRecord::new(
             drivers_with_images,
             drivers.total_items,
             drivers.page,
             drivers.per_page,
-        ))
+        )
+        .with_sort(drivers.sort))
     }
 
     pub async fn generate_driver_image_upload_url(
         &self,
         driver_id: i32,
-    ) -> Result<(String, String), ApiError> {
+        content_type: &str,
+    ) -> Result<PresignedUpload, ApiError> {
         // Check if the driver exists
         self.db_repo.get_driver_by_id(driver_id).await?;
 
@@ -187,25 +811,106 @@ impl Service {
         let file_name = format!("driver_images/{}/{}", driver_id, uuid::Uuid::new_v4());
 
         // Generate the presigned URL
-        self.bucket_repo.generate_upload_url(&file_name).await
+        self.presigned_upload(UploadPurpose::DriverPhoto, file_name, content_type)
+            .await
     }
 
     pub async fn generate_image_upload_url(
         &self,
-        prefix: &str,
-    ) -> Result<(String, String), ApiError> {
-        let file_name = format!("{}/{}", prefix, uuid::Uuid::new_v4());
+        purpose: UploadPurpose,
+        content_type: &str,
+    ) -> Result<PresignedUpload, ApiError> {
+        let file_name = format!("{}/{}", purpose.prefix(), uuid::Uuid::new_v4());
 
-        self.bucket_repo.generate_upload_url(&file_name).await
+        self.presigned_upload(purpose, file_name, content_type)
+            .await
+    }
+
+    /// Stores an image sent through the API instead of straight to the bucket,
+    /// returning the key to reference it by in `create_complaint`. The content
+    /// type is sniffed from the bytes, whatever the client claimed.
+    pub async fn upload_image(
+        &self,
+        purpose: UploadPurpose,
+        bytes: Vec<u8>,
+    ) -> Result<String, ApiError> {
+        if bytes.is_empty() {
+            return Err(ApiError::BadRequest("Uploaded file is empty".to_string()));
+        }
+        if bytes.len() as i64 > purpose.max_size_bytes() {
+            return Err(ApiError::BadRequest(format!(
+                "Files uploaded as {} must be at most {} bytes",
+                purpose.as_str(),
+                purpose.max_size_bytes()
+            )));
+        }
+
+        let allowed_content_types = purpose.allowed_content_types();
+        let content_type = infer::get(&bytes)
+            .map(|kind| kind.mime_type())
+            .filter(|content_type| allowed_content_types.contains(content_type))
+            .ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Unsupported {} file, expected one of: {}",
+                    purpose.as_str(),
+                    allowed_content_types.join(", ")
+                ))
+            })?;
+
+        let key = format!("{}/{}", purpose.prefix(), uuid::Uuid::new_v4());
+        self.db_repo.record_image_upload(&key).await?;
+        self.bucket_repo
+            .put_object(&key, bytes, content_type)
+            .await?;
+
+        Ok(key)
+    }
+
+    async fn presigned_upload(
+        &self,
+        purpose: UploadPurpose,
+        key: String,
+        content_type: &str,
+    ) -> Result<PresignedUpload, ApiError> {
+        let allowed_content_types = purpose.allowed_content_types();
+        if !allowed_content_types.contains(&content_type) {
+            return Err(ApiError::BadRequest(format!(
+                "Content type '{}' is not allowed for a {}, expected one of: {}",
+                content_type,
+                purpose.as_str(),
+                allowed_content_types.join(", ")
+            )));
+        }
+
+        let policy = UploadPolicy {
+            content_type: content_type.to_string(),
+            max_size_bytes: purpose.max_size_bytes(),
+            expires_in: purpose.expires_in(),
+        };
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::from_std(policy.expires_in)
+                .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+        let post = self.bucket_repo.generate_upload_url(&key, &policy).await?;
+        self.db_repo.record_image_upload(&key).await?;
+
+        Ok(PresignedUpload {
+            upload_url: post.url,
+            fields: post.fields,
+            key,
+            expires_at,
+        })
     }
 
+    /// Image URLs are only signed once the complaint is published, unless a
+    /// moderator is asking
     pub async fn get_complaint_with_images(
         &self,
         complaint_id: i32,
+        is_moderator: bool,
     ) -> Result<ComplaintWithImages, ApiError> {
         let complaint = self.db_repo.get_complaint_by_id(complaint_id).await?;
 
-        let images = self
+        let mut images = self
             .db_repo
             .get_complaint_images(
                 complaint_id,
@@ -215,48 +920,311 @@ impl Service {
                 },
             )
             .await?;
+        if complaint.published || is_moderator {
+            self.resolve_complaint_image_urls(&mut images.items).await?;
+        }
 
         Ok(ComplaintWithImages {
+            original_description: complaint
+                .original_description
+                .clone()
+                .filter(|_| is_moderator),
             complaint,
             images: images.items,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn search_drivers_with_details(
         &self,
         query: &str,
+        mode: PlateMatchMode,
+        filters: &ComplaintFilters,
+        sort: &Sort<DriverSortField>,
         pagination: &Pagination,
         complaints_pagination: &Pagination,
+        is_moderator: bool,
     ) -> Result<PaginatedRecord<DriverWithDetails>, ApiError> {
-        let drivers = self.db_repo.search_drivers(query, pagination).await?;
+        let filter = filters.to_filter();
+        let drivers = self
+            .db_repo
+            .search_drivers(query, mode, &filter, sort, pagination)
+            .await?;
 
-        let drivers_with_details: Result<Vec<DriverWithDetails>, ApiError> =
-            future::try_join_all(drivers.items.into_iter().map(|driver| async {
-                let complaints = self
-                    .db_repo
-                    .get_complaints_for_driver(driver.id, complaints_pagination)
-                    .await?;
-                let driver_images = self
-                    .db_repo
-                    .get_driver_images(
-                        driver.id,
-                        &Pagination {
-                            page: 1,
-                            per_page: 100,
-                        },
+        let driver_ids: Vec<i32> = drivers.items.iter().map(|driver| driver.id).collect();
+        let (mut complaints, mut images) = future::try_join(
+            self.db_repo.get_complaints_for_drivers(
+                &driver_ids,
+                &filter,
+                &Sort::default(),
+                complaints_pagination,
+            ),
+            self.db_repo.get_driver_images_for_drivers(&driver_ids, 100),
+        )
+        .await?;
+        for driver_images in images.values_mut() {
+            self.resolve_driver_image_urls(driver_images, is_moderator)
+                .await?;
+        }
+
+        let drivers_with_details: Vec<DriverWithDetails> = drivers
+            .items
+            .into_iter()
+            .map(|driver| DriverWithDetails {
+                complaints: complaints.remove(&driver.id).unwrap_or_else(|| {
+                    PaginatedRecord::new(
+                        Vec::new(),
+                        0,
+                        complaints_pagination.page,
+                        complaints_pagination.per_page,
+                    )
+                }),
+                images: images.remove(&driver.id).unwrap_or_default(),
+                driver,
+            })
+            .collect();
+
+        Ok(PaginatedRecord::new(
+            drivers_with_details,
+            drivers.total_items,
+            drivers.page,
+            drivers.per_page,
+        )
+        .with_sort(drivers.sort))
+    }
+
+    /// Pixelates regions of a complaint image. The public image is replaced by a
+    /// redacted copy, while the original is kept under a private key so it can
+    /// still be produced for legal requests.
+    pub async fn redact_complaint_image(
+        &self,
+        image_id: i32,
+        regions: Vec<RedactionRegion>,
+        moderator_id: &str,
+    ) -> Result<ComplaintImage, ApiError> {
+        if regions.is_empty() {
+            return Err(ApiError::BadRequest(
+                "At least one region must be redacted".to_string(),
+            ));
+        }
+
+        let image = self.db_repo.get_complaint_image_by_id(image_id).await?;
+        if image.processing_status != ImageProcessingStatus::Ready {
+            return Err(ApiError::BadRequest(format!(
+                "Complaint image {} has not been processed",
+                image_id
+            )));
+        }
+
+        // Redactions always start from the original, so they can be revised
+        let original_key = match &image.original_key {
+            Some(original_key) => original_key.clone(),
+            None => {
+                let original_key = format!("{}{}", PRIVATE_KEY_PREFIX, image.image_key);
+                let bytes = self.bucket_repo.get_object(&image.image_key).await?;
+                self.bucket_repo
+                    .put_object(
+                        &original_key,
+                        bytes,
+                        image.content_type.as_deref().unwrap_or("image/webp"),
                     )
                     .await?;
+                original_key
+            }
+        };
 
-                Ok(DriverWithDetails {
-                    driver,
-                    complaints,
-                    images: driver_images.items,
-                })
-            }))
-            .await;
+        let original = self.bucket_repo.get_object(&original_key).await?;
+        let redaction_regions = regions.clone();
+        let processed = tokio::task::spawn_blocking(move || {
+            image_processing::redact_image(&original, &redaction_regions)
+        })
+        .await
+        .map_err(|e| ApiError::UnexpectedError(e.to_string()))??;
+
+        // A fresh key, so the public copy says nothing about where the original is
+        let base_key = format!(
+            "{}/{}",
+            UploadPurpose::ComplaintEvidence.prefix(),
+            uuid::Uuid::new_v4()
+        );
+        let objects = self.store_processed_image(&base_key, processed).await?;
+        let mut redacted = self
+            .db_repo
+            .save_complaint_image_redaction(
+                image_id,
+                &ComplaintImageRedaction {
+                    original_key,
+                    regions,
+                    redacted_by: moderator_id.to_string(),
+                    objects,
+                },
+            )
+            .await?;
+
+        // Drop the previous public copies, unredacted or redacted earlier
+        for key in [Some(image.image_key), image.thumbnail_key]
+            .into_iter()
+            .flatten()
+        {
+            if let Err(e) = self.bucket_repo.delete_image(&key).await {
+                log::warn!("Failed to delete replaced image {}: {}", key, e);
+            }
+        }
+
+        self.resolve_complaint_image_urls(std::slice::from_mut(&mut redacted))
+            .await?;
+        Ok(redacted)
+    }
 
-        drivers_with_details.map(|details| {
-            PaginatedRecord::new(details, drivers.total_items, drivers.page, drivers.per_page)
+    /// Runs the orphaned upload collection every `interval`, forever
+    pub async fn run_upload_gc(
+        self: Arc<Self>,
+        interval: Duration,
+        grace: Duration,
+        dry_run: bool,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.collect_orphaned_uploads(grace, dry_run).await {
+                Ok(report) => log::info!(
+                    "Upload GC{}: {} orphaned of {} scanned, {} deleted, {} failed",
+                    if report.dry_run { " (dry run)" } else { "" },
+                    report.orphaned.len(),
+                    report.scanned,
+                    report.deleted,
+                    report.failed
+                ),
+                Err(e) => log::error!("Upload GC failed: {}", e),
+            }
+        }
+    }
+
+    /// Deletes uploaded objects no driver or complaint image references, once
+    /// they are older than `grace`. With `dry_run`, only reports them.
+    pub async fn collect_orphaned_uploads(
+        &self,
+        grace: Duration,
+        dry_run: bool,
+    ) -> Result<OrphanedUploadsReport, ApiError> {
+        let cutoff = chrono::Duration::from_std(grace)
+            .ok()
+            .and_then(|grace| chrono::Utc::now().checked_sub_signed(grace))
+            .ok_or_else(|| ApiError::BadRequest("Grace period is too long".to_string()))?;
+
+        let mut candidates = Vec::new();
+        for purpose in COLLECTED_UPLOAD_PURPOSES {
+            let objects = self
+                .bucket_repo
+                .list_objects(&format!("{}/", purpose.prefix()))
+                .await?;
+            candidates.extend(
+                objects
+                    .into_iter()
+                    .filter(|object| object.last_modified < cutoff),
+            );
+        }
+
+        let mut referenced = HashSet::new();
+        for batch in candidates.chunks(UPLOAD_GC_BATCH) {
+            let keys: Vec<String> = batch.iter().map(|object| object.key.clone()).collect();
+            referenced.extend(self.db_repo.get_referenced_image_keys(&keys).await?);
+        }
+
+        let scanned = candidates.len();
+        let orphaned: Vec<BucketObject> = candidates
+            .into_iter()
+            .filter(|object| !referenced.contains(&object.key))
+            .collect();
+
+        let mut deleted_keys = Vec::new();
+        if !dry_run {
+            for object in &orphaned {
+                match self.bucket_repo.delete_image(&object.key).await {
+                    Ok(()) => deleted_keys.push(object.key.clone()),
+                    Err(e) => log::warn!("Failed to delete orphaned upload {}: {}", object.key, e),
+                }
+            }
+            for batch in deleted_keys.chunks(UPLOAD_GC_BATCH) {
+                self.db_repo.delete_unattached_image_uploads(batch).await?;
+            }
+        }
+
+        Ok(OrphanedUploadsReport {
+            dry_run,
+            scanned,
+            deleted: deleted_keys.len(),
+            failed: if dry_run {
+                0
+            } else {
+                orphaned.len() - deleted_keys.len()
+            },
+            orphaned,
         })
     }
+
+    pub async fn is_moderator(&self, user_id: &str) -> Result<bool, ApiError> {
+        self.db_repo.is_moderator(user_id).await
+    }
+
+    /// Pending complaints, each with the images it shares with other drivers or
+    /// reporters. The same photo reported against several drivers, or by
+    /// several people, is a sign of spam.
+    pub async fn get_moderation_queue(
+        &self,
+        pagination: &CursorPagination,
+    ) -> Result<CursorPage<ModerationQueueItem>, ApiError> {
+        let page = self.db_repo.get_pending_complaints(pagination).await?;
+
+        let complaint_ids: Vec<i32> = page.items.iter().map(|complaint| complaint.id).collect();
+        let mut duplicates = self
+            .db_repo
+            .find_duplicate_images(
+                &complaint_ids,
+                DUPLICATE_IMAGE_MAX_DISTANCE,
+                DUPLICATE_IMAGE_WINDOW,
+            )
+            .await?;
+
+        Ok(CursorPage {
+            items: page
+                .items
+                .into_iter()
+                .map(|complaint| ModerationQueueItem {
+                    duplicate_images: duplicates.remove(&complaint.id).unwrap_or_default(),
+                    original_description: complaint.original_description.clone(),
+                    complaint,
+                })
+                .collect(),
+            next_cursor: page.next_cursor,
+            limit: page.limit,
+            total_items: page.total_items,
+        })
+    }
+
+    /// Every complaint with an image resembling the complaint image `image_id`
+    pub async fn get_complaints_sharing_image(
+        &self,
+        image_id: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SharedImageComplaint>, ApiError> {
+        if max_distance > MAX_DUPLICATE_IMAGE_DISTANCE {
+            return Err(ApiError::BadRequest(format!(
+                "Maximum distance must be at most {}",
+                MAX_DUPLICATE_IMAGE_DISTANCE
+            )));
+        }
+
+        let image = self.db_repo.get_complaint_image_by_id(image_id).await?;
+        if image.processing_status != ImageProcessingStatus::Ready {
+            return Err(ApiError::BadRequest(format!(
+                "Complaint image {} has not been processed",
+                image_id
+            )));
+        }
+
+        self.db_repo
+            .get_complaints_sharing_image(image_id, max_distance)
+            .await
+    }
 }
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// What the bucket knows about a stored object
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    pub content_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub sha256: Option<String>,
+}
+
+/// An object listed from the bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketObject {
+    pub key: String,
+    pub last_modified: chrono::DateTime<chrono::Utc>,
+    pub size_bytes: i64,
+}
+
+/// Constraints a presigned upload enforces on the uploaded object
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    pub content_type: String,
+    pub max_size_bytes: i64,
+    pub expires_in: std::time::Duration,
+}
+
+/// A form upload: the file is POSTed to `url` as multipart form data, after `fields`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresignedPost {
+    pub url: String,
+    pub fields: std::collections::BTreeMap<String, String>,
+}
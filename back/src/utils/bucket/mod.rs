@@ -0,0 +1,2 @@
+mod bucket_objects;
+pub use bucket_objects::*;
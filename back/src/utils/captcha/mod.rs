@@ -0,0 +1,5 @@
+mod siteverify_client;
+pub use siteverify_client::*;
+
+mod stub_captcha;
+pub use stub_captcha::*;
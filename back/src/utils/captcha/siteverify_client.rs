@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::error::ApiError;
+
+pub const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+pub const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+/// Give up on the provider after this long
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Error codes meaning our own configuration is wrong, not the token
+const CONFIGURATION_ERROR_CODES: [&str; 3] = [
+    "missing-input-secret",
+    "invalid-input-secret",
+    "sitekey-secret-mismatch",
+];
+
+#[derive(Deserialize)]
+struct SiteverifyResponse {
+    success: bool,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+/// Verifies CAPTCHA tokens with a siteverify endpoint. hCaptcha and Cloudflare
+/// Turnstile share the protocol: the secret and the token solved by the client
+/// are posted as a form, and the provider answers whether the token is valid.
+/// Tokens can only be verified once.
+pub struct SiteverifyClient {
+    client: reqwest::Client,
+    verify_url: String,
+    secret: String,
+}
+
+impl SiteverifyClient {
+    pub fn new(verify_url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(VERIFY_TIMEOUT)
+                .build()
+                .expect("Failed to build the CAPTCHA HTTP client"),
+            verify_url: verify_url.into(),
+            secret: secret.into(),
+        }
+    }
+
+    /// Whether the provider accepts `token`. Fails with `ServiceUnavailable`
+    /// when it cannot be reached, so submissions are refused rather than let
+    /// through unverified.
+    pub async fn verify(&self, token: &str) -> Result<bool, ApiError> {
+        let unavailable =
+            |e: reqwest::Error| ApiError::ServiceUnavailable(format!("CAPTCHA provider: {}", e));
+
+        let response: SiteverifyResponse = self
+            .client
+            .post(&self.verify_url)
+            .form(&[("secret", self.secret.as_str()), ("response", token)])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(unavailable)?
+            .json()
+            .await
+            .map_err(unavailable)?;
+
+        if let Some(code) = response
+            .error_codes
+            .iter()
+            .find(|code| CONFIGURATION_ERROR_CODES.contains(&code.as_str()))
+        {
+            return Err(ApiError::UnexpectedError(format!(
+                "CAPTCHA provider rejected our configuration: {}",
+                code
+            )));
+        }
+        if !response.success {
+            log::debug!("CAPTCHA token rejected: {:?}", response.error_codes);
+        }
+
+        Ok(response.success)
+    }
+}
@@ -0,0 +1,18 @@
+/// Accepts a single fixed token and rejects everything else, for development
+/// and tests where no CAPTCHA provider is reachable
+#[derive(Debug, Clone)]
+pub struct StubCaptcha {
+    token: String,
+}
+
+impl StubCaptcha {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+
+    pub fn verify(&self, token: &str) -> bool {
+        token == self.token
+    }
+}
@@ -1,17 +1,162 @@
-use std::env;
+use super::rate_limit::IpNetwork;
+
+/// Uploads younger than this are never collected, even when unreferenced
+pub const DEFAULT_UPLOAD_GC_GRACE_HOURS: u64 = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    S3,
+    Local,
+}
+
+/// Where rate limit counters are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitBackend {
+    Memory,
+    Postgres,
+    Disabled,
+}
+
+/// Who verifies CAPTCHA tokens
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    HCaptcha,
+    Turnstile,
+    /// Accepts `captcha_stub_token` only, for development and tests
+    Stub,
+    Disabled,
+}
+
+/// Token accepted by the stub CAPTCHA provider unless configured otherwise
+pub const DEFAULT_STUB_CAPTCHA_TOKEN: &str = "captcha-ok";
 
 pub struct Config {
     pub database_url: String,
-    pub aws_region: String,
-    pub s3_bucket: String,
+    pub aws_region: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_endpoint: Option<String>,
+    pub s3_force_path_style: bool,
+    pub s3_public_url: Option<String>,
+    pub storage_backend: StorageBackend,
+    pub local_storage_path: String,
+    pub local_storage_public_url: String,
+    pub local_storage_secret: Option<String>,
+    pub local_storage_max_upload_bytes: usize,
+    /// Seconds between garbage collections of orphaned uploads, 0 to disable them
+    pub upload_gc_interval_secs: u64,
+    pub upload_gc_grace_hours: u64,
+    /// Only report orphaned uploads instead of deleting them
+    pub upload_gc_dry_run: bool,
+    pub rate_limit_backend: RateLimitBackend,
+    /// Proxies whose X-Forwarded-For header is trusted to give the client IP
+    pub rate_limit_trusted_proxies: Vec<IpNetwork>,
+    /// Complaints a client can submit per hour, 0 for no limit
+    pub rate_limit_complaints_per_hour: u32,
+    /// Upload URLs a client can request per hour, 0 for no limit
+    pub rate_limit_upload_urls_per_hour: u32,
+    pub captcha_provider: CaptchaProvider,
+    pub captcha_secret: Option<String>,
+    /// Overrides the provider's siteverify endpoint
+    pub captcha_verify_url: Option<String>,
+    pub captcha_stub_token: String,
+    /// Whether submitting a complaint requires a CAPTCHA, when a provider is set
+    pub captcha_create_complaint: bool,
+    /// Word lists replacing the default profanity lists
+    pub profanity_wordlist_path: Option<String>,
 }
 
 impl Config {
     pub fn from_env() -> Self {
         Self {
             database_url: std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-            aws_region: std::env::var("AWS_REGION").expect("AWS_REGION must be set"),
-            s3_bucket: std::env::var("S3_BUCKET").expect("S3_BUCKET must be set"),
+            aws_region: std::env::var("AWS_REGION").ok(),
+            s3_bucket: std::env::var("S3_BUCKET").ok(),
+            s3_endpoint: std::env::var("S3_ENDPOINT").ok(),
+            s3_force_path_style: std::env::var("S3_FORCE_PATH_STYLE")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            s3_public_url: std::env::var("S3_PUBLIC_URL").ok(),
+            storage_backend: match std::env::var("STORAGE_BACKEND").as_deref() {
+                Ok("local") => StorageBackend::Local,
+                Ok("s3") | Err(_) => StorageBackend::S3,
+                Ok(other) => panic!("STORAGE_BACKEND must be 's3' or 'local', got '{}'", other),
+            },
+            local_storage_path: std::env::var("LOCAL_STORAGE_PATH")
+                .unwrap_or_else(|_| "./storage".to_string()),
+            local_storage_public_url: std::env::var("LOCAL_STORAGE_PUBLIC_URL")
+                .unwrap_or_else(|_| "http://localhost:4200/api/storage".to_string()),
+            local_storage_secret: std::env::var("LOCAL_STORAGE_SECRET").ok(),
+            local_storage_max_upload_bytes: std::env::var("LOCAL_STORAGE_MAX_UPLOAD_BYTES")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("LOCAL_STORAGE_MAX_UPLOAD_BYTES must be a number")
+                })
+                .unwrap_or(10 * 1024 * 1024),
+            upload_gc_interval_secs: std::env::var("UPLOAD_GC_INTERVAL_SECS")
+                .ok()
+                .map(|v| v.parse().expect("UPLOAD_GC_INTERVAL_SECS must be a number"))
+                .unwrap_or(6 * 60 * 60),
+            upload_gc_grace_hours: std::env::var("UPLOAD_GC_GRACE_HOURS")
+                .ok()
+                .map(|v| v.parse().expect("UPLOAD_GC_GRACE_HOURS must be a number"))
+                .unwrap_or(DEFAULT_UPLOAD_GC_GRACE_HOURS),
+            upload_gc_dry_run: std::env::var("UPLOAD_GC_DRY_RUN")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            rate_limit_backend: match std::env::var("RATE_LIMIT_STORE").as_deref() {
+                Ok("memory") | Err(_) => RateLimitBackend::Memory,
+                Ok("postgres") => RateLimitBackend::Postgres,
+                Ok("off") => RateLimitBackend::Disabled,
+                Ok(other) => panic!(
+                    "RATE_LIMIT_STORE must be 'memory', 'postgres' or 'off', got '{}'",
+                    other
+                ),
+            },
+            rate_limit_trusted_proxies: std::env::var("RATE_LIMIT_TRUSTED_PROXIES")
+                .map(|v| {
+                    v.split(',')
+                        .filter(|proxy| !proxy.trim().is_empty())
+                        .map(|proxy| {
+                            proxy.parse().unwrap_or_else(|e| {
+                                panic!("RATE_LIMIT_TRUSTED_PROXIES is invalid: {}", e)
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            rate_limit_complaints_per_hour: std::env::var("RATE_LIMIT_COMPLAINTS_PER_HOUR")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("RATE_LIMIT_COMPLAINTS_PER_HOUR must be a number")
+                })
+                .unwrap_or(10),
+            rate_limit_upload_urls_per_hour: std::env::var("RATE_LIMIT_UPLOAD_URLS_PER_HOUR")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("RATE_LIMIT_UPLOAD_URLS_PER_HOUR must be a number")
+                })
+                .unwrap_or(60),
+            captcha_provider: match std::env::var("CAPTCHA_PROVIDER").as_deref() {
+                Ok("hcaptcha") => CaptchaProvider::HCaptcha,
+                Ok("turnstile") => CaptchaProvider::Turnstile,
+                Ok("stub") => CaptchaProvider::Stub,
+                Ok("off") | Err(_) => CaptchaProvider::Disabled,
+                Ok(other) => panic!(
+                    "CAPTCHA_PROVIDER must be 'hcaptcha', 'turnstile', 'stub' or 'off', got '{}'",
+                    other
+                ),
+            },
+            captcha_secret: std::env::var("CAPTCHA_SECRET").ok(),
+            captcha_verify_url: std::env::var("CAPTCHA_VERIFY_URL").ok(),
+            captcha_stub_token: std::env::var("CAPTCHA_STUB_TOKEN")
+                .unwrap_or_else(|_| DEFAULT_STUB_CAPTCHA_TOKEN.to_string()),
+            captcha_create_complaint: std::env::var("CAPTCHA_CREATE_COMPLAINT")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(true),
+            profanity_wordlist_path: std::env::var("PROFANITY_WORDLIST_PATH").ok(),
         }
     }
 }
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "country")]
+pub enum Country {
+    Peru,
+    Mexico,
+}
+
+impl Country {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Country::Peru => "Peru",
+            Country::Mexico => "Mexico",
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use sqlx::{postgres::PgArguments, Arguments};
+use std::collections::BTreeMap;
+
+use crate::error::ApiError;
 
 #[derive(Clone, Debug)]
 pub enum Value {
@@ -9,6 +14,7 @@ pub enum Value {
     String(String),
     Bool(bool),
     Json(JsonValue),
+    Timestamp(DateTime<Utc>),
 }
 
 // Existing implementations
@@ -86,6 +92,12 @@ impl From<JsonValue> for Value {
     }
 }
 
+impl From<DateTime<Utc>> for Value {
+    fn from(v: DateTime<Utc>) -> Self {
+        Value::Timestamp(v)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum FilterCondition {
     Eq(Value),
@@ -101,15 +113,29 @@ pub enum FilterCondition {
     JsonExists(String),
 }
 
+/// A field that callers are allowed to filter on, mapped to the SQL expression it
+/// compares against. Only fields listed by the repository reach the query text.
+#[derive(Clone, Copy, Debug)]
+pub struct FilterField {
+    pub name: &'static str,
+    pub column: &'static str,
+}
+
+impl FilterField {
+    pub const fn new(name: &'static str, column: &'static str) -> Self {
+        Self { name, column }
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Filter {
-    conditions: HashMap<String, FilterCondition>,
+    conditions: BTreeMap<String, FilterCondition>,
 }
 
 impl Filter {
     pub fn new() -> Self {
         Self {
-            conditions: HashMap::new(),
+            conditions: BTreeMap::new(),
         }
     }
 
@@ -121,65 +147,95 @@ impl Filter {
         self
     }
 
-    pub fn build_for_sqlx(&self) -> (String, Vec<Value>) {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Builds a WHERE fragment for the conditions in this filter.
+    ///
+    /// Field names are resolved through `fields`, so only whitelisted columns are
+    /// interpolated; every value is returned as a bind argument. Placeholders start
+    /// at `$first_placeholder` so the fragment can be appended to a query that
+    /// already binds its own parameters.
+    pub fn build_for_sqlx(
+        &self,
+        fields: &[FilterField],
+        first_placeholder: usize,
+    ) -> Result<(String, Vec<Value>), ApiError> {
         let mut conditions = Vec::new();
         let mut args: Vec<Value> = Vec::new();
+        let next = |args: &Vec<Value>| first_placeholder + args.len();
 
         for (field, condition) in &self.conditions {
+            let column = fields
+                .iter()
+                .find(|f| f.name == field)
+                .map(|f| f.column)
+                .ok_or_else(|| ApiError::BadRequest(format!("Cannot filter by '{}'", field)))?;
+
             match condition {
                 FilterCondition::Eq(value) => {
-                    conditions.push(format!("{} = ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} = ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Ne(value) => {
-                    conditions.push(format!("{} != ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} != ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Gt(value) => {
-                    conditions.push(format!("{} > ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} > ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Lt(value) => {
-                    conditions.push(format!("{} < ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} < ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Gte(value) => {
-                    conditions.push(format!("{} >= ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} >= ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Lte(value) => {
-                    conditions.push(format!("{} <= ${}", field, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!("{} <= ${}", column, next(&args)));
+                    args.push(value.clone());
                 }
                 FilterCondition::Between(value1, value2) => {
                     conditions.push(format!(
                         "{} BETWEEN ${} AND ${}",
-                        field,
-                        args.len() + 1,
-                        args.len() + 2
+                        column,
+                        next(&args),
+                        next(&args) + 1
                     ));
-                    args.push(value_to_encode(value1));
-                    args.push(value_to_encode(value2));
+                    args.push(value1.clone());
+                    args.push(value2.clone());
                 }
                 FilterCondition::In(values) => {
+                    if values.is_empty() {
+                        conditions.push("FALSE".to_string());
+                        continue;
+                    }
                     let placeholders: Vec<String> = (0..values.len())
-                        .map(|i| format!("${}", args.len() + i + 1))
+                        .map(|i| format!("${}", next(&args) + i))
                         .collect();
-                    conditions.push(format!("{} IN ({})", field, placeholders.join(", ")));
-                    for value in values {
-                        args.push(value_to_encode(value));
-                    }
+                    conditions.push(format!("{} IN ({})", column, placeholders.join(", ")));
+                    args.extend(values.iter().cloned());
                 }
                 FilterCondition::Like(pattern) => {
-                    conditions.push(format!("{} LIKE ${}", field, args.len() + 1));
+                    conditions.push(format!("{} LIKE ${}", column, next(&args)));
                     args.push(Value::String(pattern.clone()));
                 }
                 FilterCondition::JsonContains(path, value) => {
-                    conditions.push(format!("{}->>{} @> ${}", field, path, args.len() + 1));
-                    args.push(value_to_encode(value));
+                    conditions.push(format!(
+                        "{} -> ${} @> ${}::jsonb",
+                        column,
+                        next(&args),
+                        next(&args) + 1
+                    ));
+                    args.push(Value::String(path.clone()));
+                    args.push(value.clone());
                 }
                 FilterCondition::JsonExists(path) => {
-                    conditions.push(format!("{}->>{} IS NOT NULL", field, path));
+                    conditions.push(format!("{} ->> ${} IS NOT NULL", column, next(&args)));
+                    args.push(Value::String(path.clone()));
                 }
             }
         }
@@ -190,17 +246,28 @@ impl Filter {
             conditions.join(" AND ")
         };
 
-        (where_clause, args)
+        Ok((where_clause, args))
     }
 }
 
-fn value_to_encode(value: &Value) -> Value {
-    match value {
-        Value::Int(i) => Value::Int(*i),
-        Value::Float(f) => Value::Float(*f),
-        Value::String(s) => Value::String(s.clone()),
-        Value::Bool(b) => Value::Bool(*b),
-        Value::Json(j) => Value::Json(j.clone()),
+/// Collects values into sqlx arguments, in placeholder order.
+pub fn to_pg_arguments(values: Vec<Value>) -> PgArguments {
+    let mut arguments = PgArguments::default();
+    extend_pg_arguments(&mut arguments, values);
+    arguments
+}
+
+/// Appends values to arguments that already bind the first placeholders of a query
+pub fn extend_pg_arguments(arguments: &mut PgArguments, values: Vec<Value>) {
+    for value in values {
+        match value {
+            Value::Int(i) => arguments.add(i),
+            Value::Float(f) => arguments.add(f),
+            Value::String(s) => arguments.add(s),
+            Value::Bool(b) => arguments.add(b),
+            Value::Json(j) => arguments.add(j.to_string()),
+            Value::Timestamp(t) => arguments.add(t),
+        }
     }
 }
 
@@ -257,6 +324,67 @@ pub struct Pagination {
     pub per_page: u32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// A field a listing can be sorted by. Each endpoint declares its own enum of
+/// fields, which acts as the whitelist of accepted `sort_by` values.
+pub trait SortField: Copy + Default {
+    fn name(&self) -> &'static str;
+
+    /// Order used when the request does not specify one
+    fn default_order(&self) -> SortOrder {
+        SortOrder::Asc
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Sort<F> {
+    #[serde(default)]
+    pub sort_by: F,
+    pub order: Option<SortOrder>,
+}
+
+impl<F: SortField> Sort<F> {
+    pub fn new(sort_by: F, order: SortOrder) -> Self {
+        Self {
+            sort_by,
+            order: Some(order),
+        }
+    }
+
+    pub fn order(&self) -> SortOrder {
+        self.order.unwrap_or_else(|| self.sort_by.default_order())
+    }
+
+    pub fn applied(&self) -> AppliedSort {
+        AppliedSort {
+            sort_by: self.sort_by.name().to_string(),
+            order: self.order(),
+        }
+    }
+}
+
+/// The sort a listing was actually returned in, echoed back to the client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppliedSort {
+    pub sort_by: String,
+    pub order: SortOrder,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaginatedRecord<T> {
     pub items: Vec<T>,
@@ -264,6 +392,8 @@ pub struct PaginatedRecord<T> {
     pub page: u32,
     pub per_page: u32,
     pub total_pages: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<AppliedSort>,
 }
 
 impl<T> PaginatedRecord<T> {
@@ -275,6 +405,132 @@ impl<T> PaginatedRecord<T> {
             page,
             per_page,
             total_pages,
+            sort: None,
+        }
+    }
+
+    pub fn with_sort(mut self, sort: Option<AppliedSort>) -> Self {
+        self.sort = sort;
+        self
+    }
+}
+
+/// Largest page a cursor-paginated listing returns, whatever the client asks for
+pub const MAX_CURSOR_LIMIT: u32 = 100;
+
+/// Keyset pagination: instead of a page number the client passes back the
+/// `next_cursor` of the previous page. Counting every matching row is optional
+/// because it is the expensive part of large listings.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CursorPagination {
+    pub cursor: Option<String>,
+    pub limit: u32,
+    #[serde(default)]
+    pub include_total: bool,
+}
+
+impl CursorPagination {
+    pub fn limit(&self) -> u32 {
+        self.limit.clamp(1, MAX_CURSOR_LIMIT)
+    }
+
+    pub fn decode_cursor(&self) -> Result<Option<Cursor>, ApiError> {
+        self.cursor.as_deref().map(Cursor::decode).transpose()
+    }
+}
+
+/// Position of the last item of a page in a listing ordered by `(created_at, id)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: i32) -> Self {
+        Self { created_at, id }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (micros, id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            created_at: micros
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_micros)
+                .ok_or_else(invalid)?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    /// Condition selecting the rows that come after this cursor in a listing
+    /// ordered by `(created_at_column, id_column)` in `order`
+    pub fn build_for_sqlx(
+        &self,
+        created_at_column: &str,
+        id_column: &str,
+        order: SortOrder,
+        first_placeholder: usize,
+    ) -> (String, Vec<Value>) {
+        let operator = match order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        (
+            format!(
+                "({}, {}) {} (${}, ${})",
+                created_at_column,
+                id_column,
+                operator,
+                first_placeholder,
+                first_placeholder + 1
+            ),
+            vec![Value::Timestamp(self.created_at), Value::from(self.id)],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub limit: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+}
+
+impl<T> CursorPage<T> {
+    /// Builds a page from rows fetched with `LIMIT limit + 1`: the extra row,
+    /// if present, only signals that there is a next page and is dropped.
+    pub fn new<F>(mut items: Vec<T>, limit: u32, total_items: Option<u64>, cursor_of: F) -> Self
+    where
+        F: Fn(&T) -> Cursor,
+    {
+        let next_cursor = if items.len() > limit as usize {
+            items.truncate(limit as usize);
+            items.last().map(|item| cursor_of(item).encode())
+        } else {
+            None
+        };
+
+        Self {
+            items,
+            next_cursor,
+            limit,
+            total_items,
         }
     }
 }
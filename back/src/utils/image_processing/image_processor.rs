@@ -0,0 +1,191 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImage, GenericImageView, ImageDecoder, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::error::ApiError;
+
+/// A rectangle to pixelate, in pixels of the processed image
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RedactionRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Longest side of the full-size image, larger photos are scaled down
+pub const MAX_IMAGE_DIMENSION: u32 = 2048;
+/// Longest side of thumbnails
+pub const THUMBNAIL_DIMENSION: u32 = 320;
+/// Refuse to decode anything larger than this, whatever the file size
+const MAX_DECODED_DIMENSION: u32 = 12_000;
+/// Redacted regions are split into about this many blocks along their longest side
+const REDACTION_BLOCKS: u32 = 8;
+/// Smallest block, so small regions still lose their detail
+const MIN_REDACTION_BLOCK: u32 = 12;
+/// Side of the grid compared by `perceptual_hash`, giving a 64-bit hash
+const HASH_SIZE: u32 = 8;
+/// WebP quality of processed images, high enough to keep plates and faces legible
+const WEBP_QUALITY: f32 = 80.0;
+
+/// A photo re-encoded as WebP, with its thumbnail
+pub struct ProcessedImage {
+    pub image: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// See `perceptual_hash`
+    pub perceptual_hash: u64,
+}
+
+/// Decodes an uploaded photo and re-encodes it as WebP along with a thumbnail.
+///
+/// Only the pixels survive re-encoding, so EXIF metadata (camera, GPS position
+/// of the reporter...) is dropped. The EXIF orientation is applied first so
+/// photos taken sideways still display upright. Fails with `BadRequest` when the
+/// file is not an image we can decode. This is CPU bound, so call it from a
+/// blocking task.
+pub fn process_image(bytes: &[u8]) -> Result<ProcessedImage, ApiError> {
+    let mut image = decode(bytes)?;
+    if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        image = image.resize(
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION,
+            FilterType::Lanczos3,
+        );
+    }
+
+    encode(image)
+}
+
+/// Pixelates `regions` of a processed image, returning the redacted image and
+/// a thumbnail made from it. Regions must lie within the image.
+pub fn redact_image(bytes: &[u8], regions: &[RedactionRegion]) -> Result<ProcessedImage, ApiError> {
+    let mut image = decode(bytes)?;
+    for region in regions {
+        let fits = region.width > 0
+            && region.height > 0
+            && region
+                .x
+                .checked_add(region.width)
+                .is_some_and(|right| right <= image.width())
+            && region
+                .y
+                .checked_add(region.height)
+                .is_some_and(|bottom| bottom <= image.height());
+        if !fits {
+            return Err(ApiError::BadRequest(format!(
+                "Region {}x{} at ({}, {}) is outside the {}x{} image",
+                region.width,
+                region.height,
+                region.x,
+                region.y,
+                image.width(),
+                image.height()
+            )));
+        }
+
+        pixelate(&mut image, region);
+    }
+
+    encode(image)
+}
+
+/// Replaces each block of the region by its average color
+fn pixelate(image: &mut DynamicImage, region: &RedactionRegion) {
+    let block = (region.width.max(region.height) / REDACTION_BLOCKS).max(MIN_REDACTION_BLOCK);
+    for block_y in (region.y..region.y + region.height).step_by(block as usize) {
+        for block_x in (region.x..region.x + region.width).step_by(block as usize) {
+            let width = block.min(region.x + region.width - block_x);
+            let height = block.min(region.y + region.height - block_y);
+
+            let mut sums = [0u64; 4];
+            for (_, _, pixel) in image.view(block_x, block_y, width, height).pixels() {
+                for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                    *sum += channel as u64;
+                }
+            }
+            let count = (width * height) as u64;
+            let average = image::Rgba(sums.map(|sum| (sum / count) as u8));
+
+            for y in block_y..block_y + height {
+                for x in block_x..block_x + width {
+                    image.put_pixel(x, y, average);
+                }
+            }
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<DynamicImage, ApiError> {
+    let invalid = |e: image::ImageError| ApiError::BadRequest(format!("Invalid image: {}", e));
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODED_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(invalid)?;
+    let orientation = decoder.orientation().map_err(invalid)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+    image.apply_orientation(orientation);
+
+    Ok(image)
+}
+
+/// Encodes an image and its thumbnail as WebP
+fn encode(image: DynamicImage) -> Result<ProcessedImage, ApiError> {
+    let thumbnail = image.resize(
+        THUMBNAIL_DIMENSION,
+        THUMBNAIL_DIMENSION,
+        FilterType::Triangle,
+    );
+
+    Ok(ProcessedImage {
+        width: image.width(),
+        height: image.height(),
+        perceptual_hash: perceptual_hash(&image),
+        image: encode_webp(image)?,
+        thumbnail: encode_webp(thumbnail)?,
+    })
+}
+
+/// Difference hash of an image: each bit tells whether a pixel of the image,
+/// shrunk to 9x8 grayscale pixels, is brighter than its right neighbour.
+/// Re-encoding, resizing or small edits only flip a few bits, so copies of a
+/// photo have hashes a small Hamming distance apart.
+fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let grid = image
+        .resize_exact(HASH_SIZE + 1, HASH_SIZE, FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash = 0;
+    for y in 0..HASH_SIZE {
+        for x in 0..HASH_SIZE {
+            let brighter = grid.get_pixel(x, y)[0] > grid.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+/// Lossy, through libwebp, since the only encoder `image` has for WebP is
+/// lossless, which makes photos larger than the uploads they come from
+fn encode_webp(image: DynamicImage) -> Result<Vec<u8>, ApiError> {
+    // libwebp only takes 8-bit RGB(A)
+    let (width, height) = (image.width(), image.height());
+    let (pixels, layout) = if image.color().has_alpha() {
+        (image.into_rgba8().into_raw(), webp::PixelLayout::Rgba)
+    } else {
+        (image.into_rgb8().into_raw(), webp::PixelLayout::Rgb)
+    };
+
+    let encoded = webp::Encoder::new(&pixels, layout, width, height)
+        .encode_simple(false, WEBP_QUALITY)
+        .map_err(|e| ApiError::UnexpectedError(format!("Failed to encode WebP: {:?}", e)))?;
+    Ok(encoded.to_vec())
+}
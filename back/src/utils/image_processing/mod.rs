@@ -0,0 +1,2 @@
+mod image_processor;
+pub use image_processor::*;
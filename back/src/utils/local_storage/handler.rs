@@ -0,0 +1,93 @@
+use actix_files::NamedFile;
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::error::ApiError;
+
+use super::LocalStorageRepository;
+
+/// Longest accepted value for a form field other than the file
+const MAX_FIELD_BYTES: usize = 8 * 1024;
+
+/// Receives a form upload signed by `presigned_post`. As with S3, the file must
+/// be the last field so the policy is checked before any of it is read.
+pub async fn upload_object(
+    storage: web::Data<LocalStorageRepository>,
+    mut form: Multipart,
+) -> Result<HttpResponse, ApiError> {
+    let mut fields = BTreeMap::new();
+    while let Some(mut field) = form
+        .try_next()
+        .await
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let limit = if name == "file" {
+            storage.verify_post(&fields)?.min(storage.max_upload_bytes)
+        } else {
+            MAX_FIELD_BYTES
+        };
+
+        let mut bytes = web::BytesMut::new();
+        while let Some(chunk) = field
+            .try_next()
+            .await
+            .map_err(|e| ApiError::BadRequest(e.to_string()))?
+        {
+            if bytes.len() + chunk.len() > limit {
+                return Err(ApiError::BadRequest(format!(
+                    "Field '{}' exceeds the maximum size of {} bytes",
+                    name, limit
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        if name != "file" {
+            let value = String::from_utf8(bytes.to_vec())
+                .map_err(|_| ApiError::BadRequest(format!("Field '{}' is not UTF-8", name)))?;
+            fields.insert(name, value);
+            continue;
+        }
+
+        if bytes.is_empty() {
+            return Err(ApiError::BadRequest("Uploaded file is empty".to_string()));
+        }
+        // verify_post already required the signed key
+        storage.put_object(&fields["key"], &bytes).await?;
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    Err(ApiError::BadRequest(
+        "Missing form field 'file'".to_string(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct GetObjectQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Serves an object to whoever holds a URL signed by `presigned_get`
+pub async fn get_object(
+    req: HttpRequest,
+    storage: web::Data<LocalStorageRepository>,
+    key: web::Path<String>,
+    query: web::Query<GetObjectQuery>,
+) -> Result<HttpResponse, ApiError> {
+    storage.verify_get(&key, query.expires, &query.signature)?;
+
+    let file = NamedFile::open_async(storage.object_path(&key)?)
+        .await
+        .map_err(|_| ApiError::NotFound(format!("Object {} not found", key)))?;
+    Ok(file.into_response(&req))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::post().to(upload_object)))
+        .service(web::resource("/{key:.*}").route(web::get().to(get_object)));
+}
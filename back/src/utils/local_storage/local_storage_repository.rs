@@ -0,0 +1,454 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::ApiError;
+use crate::utils::bucket::{BucketObject, ObjectMetadata, PresignedPost, UploadPolicy};
+use crate::utils::Config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Stores bucket objects as files under a root directory. Uploads are form
+/// posts and downloads are GETs, both signed with a server-side secret,
+/// mirroring S3 POST policies and presigned URLs.
+#[derive(Debug, Clone)]
+pub struct LocalStorageRepository {
+    pub root: Arc<PathBuf>,
+    pub public_url: String,
+    pub max_upload_bytes: usize,
+    secret: Arc<Vec<u8>>,
+}
+
+impl LocalStorageRepository {
+    pub async fn new() -> Result<Self, ApiError> {
+        let config = Config::from_env();
+
+        let secret = match config.local_storage_secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                log::warn!(
+                    "LOCAL_STORAGE_SECRET is not set, upload URLs will not survive a restart"
+                );
+                uuid::Uuid::new_v4().as_bytes().to_vec()
+            }
+        };
+
+        Self::with_root(
+            config.local_storage_path,
+            &config.local_storage_public_url,
+            secret,
+            config.local_storage_max_upload_bytes,
+        )
+        .await
+    }
+
+    /// Creates a repository rooted at `root`, e.g. a temporary directory in tests
+    pub async fn with_root(
+        root: impl Into<PathBuf>,
+        public_url: &str,
+        secret: impl Into<Vec<u8>>,
+        max_upload_bytes: usize,
+    ) -> Result<Self, ApiError> {
+        let root = root.into();
+        tokio::fs::create_dir_all(&root).await.map_err(|e| {
+            ApiError::UnexpectedError(format!("Failed to create {}: {}", root.display(), e))
+        })?;
+
+        Ok(Self {
+            root: Arc::new(root),
+            public_url: public_url.trim_end_matches('/').to_string(),
+            max_upload_bytes,
+            secret: Arc::new(secret.into()),
+        })
+    }
+
+    pub fn object_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+
+    /// Resolves a key to a path inside the root, rejecting keys that could escape it
+    pub fn object_path(&self, key: &str) -> Result<PathBuf, ApiError> {
+        let relative = Path::new(key);
+        let is_safe = !key.is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_safe {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid object key '{}'",
+                key
+            )));
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    /// Signs a form upload of a single object, like an S3 POST policy
+    pub fn presigned_post(&self, key: &str, policy: &UploadPolicy) -> PresignedPost {
+        let expires = chrono::Utc::now().timestamp() + policy.expires_in.as_secs() as i64;
+        let mut fields = BTreeMap::from([
+            ("key".to_string(), key.to_string()),
+            ("Content-Type".to_string(), policy.content_type.clone()),
+            ("max-size".to_string(), policy.max_size_bytes.to_string()),
+            ("expires".to_string(), expires.to_string()),
+        ]);
+        fields.insert("signature".to_string(), self.sign("POST", &fields));
+
+        PresignedPost {
+            url: self.public_url.clone(),
+            fields,
+        }
+    }
+
+    /// Checks form fields produced by `presigned_post`, returning the signed size limit
+    pub fn verify_post(&self, fields: &BTreeMap<String, String>) -> Result<usize, ApiError> {
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .ok_or_else(|| ApiError::Forbidden(format!("Missing form field '{}'", name)))
+        };
+
+        field("key")?;
+        let expires: i64 = field("expires")?
+            .parse()
+            .map_err(|_| ApiError::Forbidden("Invalid upload expiry".to_string()))?;
+        if expires < chrono::Utc::now().timestamp() {
+            return Err(ApiError::Forbidden("Upload URL has expired".to_string()));
+        }
+
+        let signature = hex::decode(field("signature")?)
+            .map_err(|_| ApiError::Forbidden("Invalid upload signature".to_string()))?;
+        let signed_fields: BTreeMap<String, String> = fields
+            .iter()
+            .filter(|(name, _)| name.as_str() != "signature")
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        self.mac("POST", &signed_fields)
+            .verify_slice(&signature)
+            .map_err(|_| ApiError::Forbidden("Invalid upload signature".to_string()))?;
+
+        field("max-size")?
+            .parse()
+            .map_err(|_| ApiError::Forbidden("Invalid upload size limit".to_string()))
+    }
+
+    /// Signs a download of a single object, valid for `expires_in` from `signed_at`
+    pub fn presigned_get(
+        &self,
+        key: &str,
+        signed_at: chrono::DateTime<chrono::Utc>,
+        expires_in: Duration,
+    ) -> String {
+        let expires = signed_at.timestamp() + expires_in.as_secs() as i64;
+        let signature = self.sign("GET", &Self::get_fields(key, expires));
+
+        format!(
+            "{}?expires={}&signature={}",
+            self.object_url(key),
+            expires,
+            signature
+        )
+    }
+
+    /// Checks the query of a URL produced by `presigned_get`
+    pub fn verify_get(&self, key: &str, expires: i64, signature: &str) -> Result<(), ApiError> {
+        if expires < chrono::Utc::now().timestamp() {
+            return Err(ApiError::Forbidden("Download URL has expired".to_string()));
+        }
+
+        let signature = hex::decode(signature)
+            .map_err(|_| ApiError::Forbidden("Invalid download signature".to_string()))?;
+        self.mac("GET", &Self::get_fields(key, expires))
+            .verify_slice(&signature)
+            .map_err(|_| ApiError::Forbidden("Invalid download signature".to_string()))
+    }
+
+    fn get_fields(key: &str, expires: i64) -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("key".to_string(), key.to_string()),
+            ("expires".to_string(), expires.to_string()),
+        ])
+    }
+
+    pub async fn get_object(&self, key: &str) -> Result<Vec<u8>, ApiError> {
+        match tokio::fs::read(self.object_path(key)?).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(ApiError::NotFound(format!("Object {} not found", key)))
+            }
+            Err(e) => Err(ApiError::UnexpectedError(e.to_string())),
+        }
+    }
+
+    /// Walks the files under the root whose key starts with `prefix`
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, ApiError> {
+        let io_error = |e: std::io::Error| ApiError::UnexpectedError(e.to_string());
+
+        let mut objects = Vec::new();
+        let mut directories = vec![self.root.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = tokio::fs::read_dir(&directory).await.map_err(io_error)?;
+            while let Some(entry) = entries.next_entry().await.map_err(io_error)? {
+                let metadata = entry.metadata().await.map_err(io_error)?;
+                if metadata.is_dir() {
+                    directories.push(entry.path());
+                    continue;
+                }
+
+                let Ok(relative) = entry
+                    .path()
+                    .strip_prefix(self.root.as_ref())
+                    .map(Path::to_path_buf)
+                else {
+                    continue;
+                };
+                let key = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !key.starts_with(prefix) {
+                    continue;
+                }
+
+                objects.push(BucketObject {
+                    key,
+                    last_modified: metadata.modified().map_err(io_error)?.into(),
+                    size_bytes: metadata.len() as i64,
+                });
+            }
+        }
+
+        Ok(objects)
+    }
+
+    pub async fn put_object(&self, key: &str, bytes: &[u8]) -> Result<(), ApiError> {
+        let path = self.object_path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+        }
+
+        tokio::fs::write(&path, bytes)
+            .await
+            .map_err(|e| ApiError::UnexpectedError(e.to_string()))
+    }
+
+    /// Reads an object to report its size and checksum. Files carry no content
+    /// type of their own, so it is sniffed from the leading bytes.
+    pub async fn object_metadata(&self, key: &str) -> Result<ObjectMetadata, ApiError> {
+        let bytes = self.get_object(key).await?;
+
+        Ok(ObjectMetadata {
+            content_type: infer::get(&bytes).map(|kind| kind.mime_type().to_string()),
+            size_bytes: Some(bytes.len() as i64),
+            sha256: Some(hex::encode(Sha256::digest(&bytes))),
+        })
+    }
+
+    pub async fn delete_object(&self, key: &str) -> Result<(), ApiError> {
+        match tokio::fs::remove_file(self.object_path(key)?).await {
+            Ok(()) => Ok(()),
+            // Match S3, where deleting a missing object succeeds
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(ApiError::UnexpectedError(e.to_string())),
+        }
+    }
+
+    /// MAC over every signed field, so none can be changed or added. The method
+    /// keeps a download signature from passing as an upload one and back.
+    fn mac(&self, method: &str, fields: &BTreeMap<String, String>) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(format!("{}\n", method).as_bytes());
+        for (name, value) in fields {
+            mac.update(format!("{}={}\n", name, value).as_bytes());
+        }
+        mac
+    }
+
+    fn sign(&self, method: &str, fields: &BTreeMap<String, String>) -> String {
+        hex::encode(self.mac(method, fields).finalize().into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "complaint_images/photo";
+
+    async fn storage() -> LocalStorageRepository {
+        let root = std::env::temp_dir().join(format!("local-storage-{}", uuid::Uuid::new_v4()));
+        LocalStorageRepository::with_root(root, "http://localhost/api/storage/", "secret", 1024)
+            .await
+            .unwrap()
+    }
+
+    fn policy() -> UploadPolicy {
+        UploadPolicy {
+            content_type: "image/jpeg".to_string(),
+            max_size_bytes: 512,
+            expires_in: Duration::from_secs(60),
+        }
+    }
+
+    /// The `expires` and `signature` query parameters of a signed GET URL
+    fn get_query(url: &str) -> (i64, String) {
+        let (_, query) = url.split_once('?').unwrap();
+        let param = |name: &str| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix(&format!("{}=", name)))
+                .unwrap()
+                .to_string()
+        };
+        (param("expires").parse().unwrap(), param("signature"))
+    }
+
+    #[tokio::test]
+    async fn post_signature_round_trips() {
+        let storage = storage().await;
+        let post = storage.presigned_post(KEY, &policy());
+
+        assert_eq!(post.url, "http://localhost/api/storage");
+        assert_eq!(storage.verify_post(&post.fields).unwrap(), 512);
+    }
+
+    #[tokio::test]
+    async fn post_with_tampered_fields_is_rejected() {
+        let storage = storage().await;
+        let post = storage.presigned_post(KEY, &policy());
+
+        for (name, value) in [
+            ("key", "complaint_images/other"),
+            ("Content-Type", "text/html"),
+            ("max-size", "999999999"),
+            ("signature", "00"),
+        ] {
+            let mut fields = post.fields.clone();
+            fields.insert(name.to_string(), value.to_string());
+            assert!(
+                matches!(storage.verify_post(&fields), Err(ApiError::Forbidden(_))),
+                "changing '{}' was accepted",
+                name
+            );
+        }
+
+        let mut fields = post.fields.clone();
+        fields.insert("acl".to_string(), "public-read".to_string());
+        assert!(matches!(
+            storage.verify_post(&fields),
+            Err(ApiError::Forbidden(_))
+        ));
+
+        let mut fields = post.fields;
+        fields.remove("signature");
+        assert!(matches!(
+            storage.verify_post(&fields),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_post_is_rejected() {
+        let storage = storage().await;
+        let mut fields = storage.presigned_post(KEY, &policy()).fields;
+        fields.remove("signature");
+        fields.insert(
+            "expires".to_string(),
+            (chrono::Utc::now().timestamp() - 1).to_string(),
+        );
+        fields.insert("signature".to_string(), storage.sign("POST", &fields));
+
+        match storage.verify_post(&fields) {
+            Err(ApiError::Forbidden(message)) => assert!(message.contains("expired")),
+            other => panic!("expected an expired upload, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_signature_round_trips() {
+        let storage = storage().await;
+        let url = storage.presigned_get(KEY, chrono::Utc::now(), Duration::from_secs(60));
+        let (expires, signature) = get_query(&url);
+
+        assert!(url.starts_with("http://localhost/api/storage/complaint_images/photo?"));
+        assert!(storage.verify_get(KEY, expires, &signature).is_ok());
+        assert!(storage
+            .verify_get("complaint_images/other", expires, &signature)
+            .is_err());
+        assert!(storage.verify_get(KEY, expires + 60, &signature).is_err());
+    }
+
+    #[tokio::test]
+    async fn signatures_from_another_secret_are_rejected() {
+        let storage = storage().await;
+        let other = LocalStorageRepository::with_root(
+            storage.root.as_ref(),
+            "http://localhost/api/storage",
+            "other secret",
+            1024,
+        )
+        .await
+        .unwrap();
+
+        let post = other.presigned_post(KEY, &policy());
+        assert!(storage.verify_post(&post.fields).is_err());
+        let (expires, signature) =
+            get_query(&other.presigned_get(KEY, chrono::Utc::now(), Duration::from_secs(60)));
+        assert!(storage.verify_get(KEY, expires, &signature).is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_get_is_rejected() {
+        let storage = storage().await;
+        let signed_at = chrono::Utc::now() - chrono::Duration::seconds(120);
+        let (expires, signature) =
+            get_query(&storage.presigned_get(KEY, signed_at, Duration::from_secs(60)));
+
+        match storage.verify_get(KEY, expires, &signature) {
+            Err(ApiError::Forbidden(message)) => assert!(message.contains("expired")),
+            other => panic!("expected an expired download, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn post_signature_is_not_accepted_as_get() {
+        let storage = storage().await;
+        let expires = chrono::Utc::now().timestamp() + 60;
+        let signature = storage.sign("POST", &LocalStorageRepository::get_fields(KEY, expires));
+
+        assert!(storage.verify_get(KEY, expires, &signature).is_err());
+    }
+
+    #[tokio::test]
+    async fn keys_escaping_the_root_are_rejected() {
+        let storage = storage().await;
+
+        for key in [
+            "",
+            "../secret",
+            "complaint_images/../../secret",
+            "./photo",
+            "/etc/passwd",
+        ] {
+            assert!(
+                matches!(storage.object_path(key), Err(ApiError::BadRequest(_))),
+                "key '{}' was accepted",
+                key
+            );
+            assert!(storage.get_object(key).await.is_err());
+            assert!(storage.put_object(key, b"data").await.is_err());
+        }
+
+        assert_eq!(
+            storage.object_path(KEY).unwrap(),
+            storage.root.join("complaint_images").join("photo")
+        );
+    }
+}
@@ -0,0 +1,5 @@
+mod local_storage_repository;
+pub use local_storage_repository::*;
+
+mod handler;
+pub use handler::config;
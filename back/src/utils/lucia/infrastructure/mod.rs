@@ -1,2 +1,3 @@
 mod pg_adatper;
+#[allow(unused_imports)]
 pub use pg_adatper::*;
@@ -1,7 +1,17 @@
 mod config;
 pub use config::*;
 
+mod country;
+pub use country::*;
+
+pub mod bucket;
+pub mod captcha;
 pub mod database;
+pub mod image_processing;
+pub mod local_storage;
 pub mod lucia;
+pub mod pii;
+pub mod profanity;
+pub mod rate_limit;
 
 pub mod s3;
@@ -0,0 +1,2 @@
+mod pii_detector;
+pub use pii_detector::*;
@@ -0,0 +1,252 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+
+use crate::utils::Country;
+
+/// Personal data found in complaint descriptions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "pii_kind", rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    /// Peruvian national ID number
+    Dni,
+    /// Mexican population registry code
+    Curp,
+    Address,
+}
+
+impl PgHasArrayType for PiiKind {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_pii_kind")
+    }
+}
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b").unwrap()
+});
+/// Digits grouped by spaces, dots, dashes or parentheses, as phone and ID
+/// numbers are usually written
+static NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\+?\(?\d(?:[\d\s().-]*\d)?").unwrap());
+/// A single group of a `NUMBER`, for numbers written next to another one
+static NUMBER_GROUP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\+?\(?\d(?:[\d().-]*\d)?").unwrap());
+/// Mexican CURP: initials, birth date, sex, state and check characters
+static CURP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b[A-Z][AEIOUX][A-Z]{2}\d{2}(?:0[1-9]|1[0-2])(?:0[1-9]|[12]\d|3[01])[HMX](?:AS|B[CS]|C[CLMSH]|D[FG]|G[TR]|HG|JC|M[CNS]|N[ETL]|OC|PL|Q[TR]|S[PLR]|T[CSL]|VZ|YN|ZS|NE)[B-DF-HJ-NP-TV-Z]{3}[A-Z\d]\d\b",
+    )
+    .unwrap()
+});
+/// A street with a house number, or a Peruvian block and lot. Streets named
+/// without a number are left alone, they usually say where the incident was.
+/// The number must directly follow a capitalized street name ("Av. 28 de Julio
+/// 1234") or a `#`/`n°` marker, so sentences going on after a street name
+/// ("en la Av. Arequipa y me cobró 30 soles") are not taken for addresses.
+static ADDRESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:(?i:av(?:enida)?|calle|jr|jir[oó]n|psje|pasaje|calz(?:ada)?|blvd|bulevar|boulevard|prolongaci[oó]n|prol)\.?\s+(?:(?:(?:de|del|la|las|los|el)\s+)*(?:\p{Lu}[\p{L}'.]*|\d{1,3})(?:\s+(?:(?:de|del|la|las|los|el)\s+)*(?:\p{Lu}[\p{L}'.]*|\d{1,3})){0,4}\s*,?\s*(?:(?:#|(?i:n[°º]|nro\.?|n\.))\s*)?|[\p{L}\d.' ]{1,40}?\s*(?:#|(?i:n[°º]))\s*)\d{1,5}\b|(?i:mz|manzana)\.?\s*[A-Z\d]{1,3}\s*,?\s*(?i:lt|lote)\.?\s*\d{1,4}\b)(?:\s*,?\s*(?i:int(?:erior)?|dpto|depto|departamento)\.?\s*[A-Z\d]{1,4}\b)?",
+    )
+    .unwrap()
+});
+/// "DNI" right before a number, which makes it a DNI however it is written
+static DNI_LABEL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bdni\s*(?:n[°º.o]\s*)?:?\s*$").unwrap());
+
+/// A description with its personal data masked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiScan {
+    pub masked: String,
+    /// Kinds of data found, without repetitions
+    pub kinds: Vec<PiiKind>,
+}
+
+/// Finds emails, phone numbers, national ID numbers and addresses in `text`
+/// and replaces each by a placeholder naming what was there. Numbers are read
+/// with the formats of `country`, or of every supported country when unknown.
+pub fn mask_pii(text: &str, country: Option<Country>) -> PiiScan {
+    let countries = match country {
+        Some(country) => vec![country],
+        None => vec![Country::Peru, Country::Mexico],
+    };
+
+    let mut found: Vec<(usize, usize, PiiKind)> = Vec::new();
+    let mut find = |regex: &Regex, kind: PiiKind| {
+        found.extend(regex.find_iter(text).map(|m| (m.start(), m.end(), kind)));
+    };
+    find(&EMAIL, PiiKind::Email);
+    find(&CURP, PiiKind::Curp);
+    find(&ADDRESS, PiiKind::Address);
+    for number in NUMBER.find_iter(text) {
+        let labelled_dni = DNI_LABEL.is_match(&text[..number.start()]);
+        if let Some(kind) = classify_number(number.as_str(), &countries, labelled_dni) {
+            found.push((number.start(), number.end(), kind));
+            continue;
+        }
+        for group in NUMBER_GROUP.find_iter(number.as_str()) {
+            if let Some(kind) = classify_number(group.as_str(), &countries, false) {
+                let start = number.start() + group.start();
+                found.push((start, start + group.len(), kind));
+            }
+        }
+    }
+
+    // Earliest and then longest match wins where matches overlap
+    found.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+    let mut masked = String::with_capacity(text.len());
+    let mut kinds = Vec::new();
+    let mut position = 0;
+    for (start, end, kind) in found {
+        if start < position {
+            continue;
+        }
+        masked.push_str(&text[position..start]);
+        masked.push_str(placeholder(kind));
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+        position = end;
+    }
+    masked.push_str(&text[position..]);
+
+    PiiScan { masked, kinds }
+}
+
+/// Whether a number looks like a phone or ID number in one of `countries`.
+/// Eight digits are only a DNI when written without separators, or right after
+/// "DNI", since dates like 15-03-2024 have eight digits too.
+fn classify_number(number: &str, countries: &[Country], labelled_dni: bool) -> Option<PiiKind> {
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    let unseparated = digits.len() == number.len();
+    countries.iter().find_map(|country| match country {
+        Country::Peru => match digits.len() {
+            // Mobile numbers, with or without the country code, and Lima landlines
+            9 if digits.starts_with('9') || digits.starts_with("01") => Some(PiiKind::Phone),
+            11 if digits.starts_with("519") => Some(PiiKind::Phone),
+            8 if unseparated || labelled_dni => Some(PiiKind::Dni),
+            _ => None,
+        },
+        Country::Mexico => match digits.len() {
+            10 => Some(PiiKind::Phone),
+            12 if digits.starts_with("52") => Some(PiiKind::Phone),
+            13 if digits.starts_with("521") => Some(PiiKind::Phone),
+            _ => None,
+        },
+    })
+}
+
+fn placeholder(kind: PiiKind) -> &'static str {
+    match kind {
+        PiiKind::Email => "[correo oculto]",
+        PiiKind::Phone => "[teléfono oculto]",
+        PiiKind::Dni => "[DNI oculto]",
+        PiiKind::Curp => "[CURP oculto]",
+        PiiKind::Address => "[dirección oculta]",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str, country: Country) -> Vec<PiiKind> {
+        mask_pii(text, Some(country)).kinds
+    }
+
+    #[test]
+    fn masks_emails() {
+        let scan = mask_pii("Escríbanme a juan.perez@gmail.com por favor", None);
+        assert_eq!(scan.masked, "Escríbanme a [correo oculto] por favor");
+        assert_eq!(scan.kinds, vec![PiiKind::Email]);
+    }
+
+    #[test]
+    fn masks_peruvian_phones_and_dnis() {
+        let scan = mask_pii(
+            "Llámenme al 987 654 321 o al +51 912345678, mi DNI es 45678912",
+            Some(Country::Peru),
+        );
+        assert_eq!(
+            scan.masked,
+            "Llámenme al [teléfono oculto] o al [teléfono oculto], mi DNI es [DNI oculto]"
+        );
+        assert_eq!(kinds("DNI: 45.678.912", Country::Peru), vec![PiiKind::Dni]);
+    }
+
+    #[test]
+    fn masks_mexican_phones_and_curps() {
+        let scan = mask_pii(
+            "Mi número es 55 1234 5678 y mi CURP GODE561231HDFRRN09",
+            Some(Country::Mexico),
+        );
+        assert_eq!(
+            scan.masked,
+            "Mi número es [teléfono oculto] y mi CURP [CURP oculto]"
+        );
+        assert_eq!(scan.kinds, vec![PiiKind::Phone, PiiKind::Curp]);
+    }
+
+    #[test]
+    fn keeps_dates_and_amounts() {
+        for country in [Country::Peru, Country::Mexico] {
+            for text in [
+                "Pasó el 15-03-2024 a las 10.30 de la noche",
+                "Me cobró 30 soles por 5 km",
+                "Fue el 15.03.2024",
+            ] {
+                assert_eq!(kinds(text, country), vec![], "{:?} in {:?}", text, country);
+            }
+        }
+    }
+
+    #[test]
+    fn eight_digits_are_only_a_dni_in_peru() {
+        assert_eq!(
+            kinds("Mi DNI es 45678912", Country::Peru),
+            vec![PiiKind::Dni]
+        );
+        assert_eq!(kinds("Código 45678912", Country::Mexico), vec![]);
+    }
+
+    #[test]
+    fn masks_addresses() {
+        for (text, country) in [
+            ("Vivo en Av. Arequipa 1234, dpto 301", Country::Peru),
+            ("Me dejó en Av. 28 de Julio 456", Country::Peru),
+            ("Mi casa está en Jr. de la Unión n° 789", Country::Peru),
+            ("Vivo en la Mz. B Lt. 12 de San Juan", Country::Peru),
+            ("Me recogió en Calzada de Tlalpan 2150", Country::Mexico),
+            ("Vivo en calle los olivos #45", Country::Mexico),
+        ] {
+            assert_eq!(
+                kinds(text, country),
+                vec![PiiKind::Address],
+                "{:?} in {:?}",
+                text,
+                country
+            );
+        }
+        assert_eq!(
+            mask_pii("Vivo en Av. Arequipa 1234, dpto 301", Some(Country::Peru)).masked,
+            "Vivo en [dirección oculta]"
+        );
+    }
+
+    #[test]
+    fn keeps_streets_without_a_house_number() {
+        for country in [Country::Peru, Country::Mexico] {
+            for text in [
+                "Tomé el taxi en la Av. Arequipa y me cobró 30 soles",
+                "en la calle me cobró 50",
+                "Me bajó en la avenida principal después de 20 minutos",
+            ] {
+                assert_eq!(kinds(text, country), vec![], "{:?} in {:?}", text, country);
+            }
+        }
+    }
+}
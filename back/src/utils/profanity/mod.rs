@@ -0,0 +1,2 @@
+mod profanity_filter;
+pub use profanity_filter::*;
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::ApiError;
+use crate::utils::Country;
+
+/// How offensive a filtered term is, which decides what happens to it
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "profanity_severity", rename_all = "snake_case")]
+pub enum ProfanitySeverity {
+    /// Masked in the published text
+    Mild,
+    /// Slurs and the like, sent to moderators as they are
+    Severe,
+}
+
+/// Word lists used unless `PROFANITY_WORDLIST_PATH` points to others
+const DEFAULT_WORD_LISTS: &str = include_str!("../../../wordlists/profanity_es.json");
+
+/// Terms to filter and words never to filter, in the format of
+/// `wordlists/profanity_es.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct WordLists {
+    pub terms: Vec<WordListTerm>,
+    #[serde(default)]
+    pub allow: Vec<AllowedWord>,
+}
+
+/// A word to filter. A trailing `*` also matches every word starting with
+/// the term, such as its inflections.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WordListTerm {
+    pub term: String,
+    pub severity: ProfanitySeverity,
+    /// Countries where the term is offensive, all of them when empty
+    #[serde(default)]
+    pub countries: Vec<Country>,
+}
+
+/// A word that is never filtered, even when it matches a term
+#[derive(Debug, Clone, Deserialize)]
+pub struct AllowedWord {
+    pub word: String,
+    #[serde(default)]
+    pub countries: Vec<Country>,
+}
+
+impl WordLists {
+    pub fn from_json(json: &str) -> Result<Self, ApiError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ApiError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| ApiError::UnexpectedError(format!("Failed to read {}: {}", path, e)))?;
+        Self::from_json(&json)
+    }
+}
+
+impl Default for WordLists {
+    fn default() -> Self {
+        Self::from_json(DEFAULT_WORD_LISTS).expect("The default word lists are invalid")
+    }
+}
+
+/// A text with its mild terms masked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfanityScan {
+    pub masked: String,
+    /// Highest severity among the terms found, if any
+    pub severity: Option<ProfanitySeverity>,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    normalized: String,
+    prefix: bool,
+    severity: ProfanitySeverity,
+    countries: Vec<Country>,
+}
+
+/// Finds Spanish profanity and slurs in texts. Words are compared after
+/// lowercasing, removing accents, reading leetspeak (`p3nd3j0`), dropping
+/// separators (`m.i.e.r.d.a`) and collapsing repeated letters (`putaaa`).
+#[derive(Debug, Clone)]
+pub struct ProfanityFilter {
+    terms: Vec<Term>,
+    /// Normalized allowed words, with the countries they are allowed in
+    allowed: HashMap<String, Vec<Country>>,
+}
+
+impl ProfanityFilter {
+    pub fn new(word_lists: WordLists) -> Self {
+        let terms = word_lists
+            .terms
+            .into_iter()
+            .map(|term| {
+                let prefix = term.term.ends_with('*');
+                Term {
+                    normalized: normalize(term.term.trim_end_matches('*')),
+                    prefix,
+                    severity: term.severity,
+                    countries: term.countries,
+                }
+            })
+            .filter(|term| !term.normalized.is_empty())
+            .collect();
+
+        let mut allowed: HashMap<String, Vec<Country>> = HashMap::new();
+        for word in word_lists.allow {
+            allowed
+                .entry(normalize(&word.word))
+                .or_default()
+                .extend(word.countries);
+        }
+
+        Self { terms, allowed }
+    }
+
+    /// Masks the mild terms of `text`, keeping their first letter, and reports
+    /// the highest severity found. Severe terms are left for a moderator to
+    /// judge in context. Terms limited to other countries than `country` are
+    /// ignored; all terms apply when it is unknown.
+    pub fn scan(&self, text: &str, country: Option<Country>) -> ProfanityScan {
+        let mut masked = String::with_capacity(text.len());
+        let mut severity = None;
+        let mut position = 0;
+        for (start, word) in words(text) {
+            let Some(found) = self.severity_of(word, country) else {
+                continue;
+            };
+            severity = severity.max(Some(found));
+            if found == ProfanitySeverity::Mild {
+                masked.push_str(&text[position..start]);
+                masked.push_str(&mask(word));
+                position = start + word.len();
+            }
+        }
+        masked.push_str(&text[position..]);
+
+        ProfanityScan { masked, severity }
+    }
+
+    fn severity_of(&self, word: &str, country: Option<Country>) -> Option<ProfanitySeverity> {
+        let applies = |countries: &[Country]| {
+            countries.is_empty() || country.is_none_or(|country| countries.contains(&country))
+        };
+
+        let normalized = normalize(word);
+        if self
+            .allowed
+            .get(&normalized)
+            .is_some_and(|countries| applies(countries))
+        {
+            return None;
+        }
+
+        self.terms
+            .iter()
+            .filter(|term| applies(&term.countries))
+            .filter(|term| {
+                normalized == term.normalized
+                    || (term.prefix && normalized.starts_with(&term.normalized))
+            })
+            .map(|term| term.severity)
+            .max()
+    }
+}
+
+impl Default for ProfanityFilter {
+    fn default() -> Self {
+        Self::new(WordLists::default())
+    }
+}
+
+/// Words of a text with their byte offset. Symbols standing for letters in
+/// leetspeak stay part of the word, other punctuation around it does not.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_word_char = |c: char| c.is_alphanumeric() || matches!(c, '@' | '$' | '.' | '-' | '_');
+    let is_separator = |c: char| matches!(c, '.' | '-' | '_');
+
+    text.split(move |c: char| !is_word_char(c))
+        .map(move |word| {
+            let trimmed = word.trim_matches(is_separator);
+            let start = trimmed.as_ptr() as usize - text.as_ptr() as usize;
+            (start, trimmed)
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+/// Lowercase letters without accents, separators or repetitions
+fn normalize(word: &str) -> String {
+    let mut normalized = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            'á' | 'à' | 'ä' | 'â' | '4' | '@' => 'a',
+            'é' | 'è' | 'ë' | 'ê' | '3' => 'e',
+            'í' | 'ì' | 'ï' | 'î' | '1' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' | '0' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            '5' | '$' => 's',
+            '7' => 't',
+            '.' | '-' | '_' => continue,
+            c => c,
+        };
+        if !normalized.ends_with(c) {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+fn mask(word: &str) -> String {
+    let mut chars = word.chars();
+    let first = chars.next().map(String::from).unwrap_or_default();
+    first + &"*".repeat(chars.count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(text: &str, country: Option<Country>) -> ProfanityScan {
+        ProfanityFilter::default().scan(text, country)
+    }
+
+    #[test]
+    fn reads_leetspeak_separators_and_repetitions() {
+        let scan = scan(
+            "Eres un p3nd3j0, qué m.i.e.r.d.a, putaaa",
+            Some(Country::Peru),
+        );
+        assert_eq!(scan.masked, "Eres un p******, qué m**********, p*****");
+        assert_eq!(scan.severity, Some(ProfanitySeverity::Mild));
+    }
+
+    #[test]
+    fn keeps_clean_text() {
+        let text = "El conductor fue muy amable y puntual";
+        assert_eq!(
+            scan(text, None),
+            ProfanityScan {
+                masked: text.to_string(),
+                severity: None,
+            }
+        );
+    }
+
+    #[test]
+    fn allowed_words_win_over_terms() {
+        let text = "Qué vergüenza de servicio, fue vergonzoso";
+        assert_eq!(scan(text, Some(Country::Mexico)).masked, text);
+        assert_eq!(scan(text, Some(Country::Mexico)).severity, None);
+
+        let scan = scan("No vale v3rga", Some(Country::Mexico));
+        assert_eq!(scan.masked, "No vale v****");
+        assert_eq!(scan.severity, Some(ProfanitySeverity::Mild));
+    }
+
+    #[test]
+    fn severity_depends_on_the_country() {
+        let peru = scan("Ese puto taxista", Some(Country::Peru));
+        assert_eq!(peru.masked, "Ese p*** taxista");
+        assert_eq!(peru.severity, Some(ProfanitySeverity::Mild));
+
+        // Severe terms are left for moderators to judge in context
+        let mexico = scan("Ese puto taxista", Some(Country::Mexico));
+        assert_eq!(mexico.masked, "Ese puto taxista");
+        assert_eq!(mexico.severity, Some(ProfanitySeverity::Severe));
+
+        // Unknown countries get the most severe reading
+        assert_eq!(
+            scan("Ese puto taxista", None).severity,
+            Some(ProfanitySeverity::Severe)
+        );
+        // Terms limited to other countries are ignored
+        assert_eq!(scan("No vale verga", Some(Country::Peru)).severity, None);
+    }
+
+    #[test]
+    fn custom_word_lists_replace_the_defaults() {
+        let filter = ProfanityFilter::new(
+            WordLists::from_json(r#"{"terms": [{"term": "tonto", "severity": "mild"}]}"#).unwrap(),
+        );
+        assert_eq!(filter.scan("Qué tonto", None).masked, "Qué t****");
+        assert_eq!(filter.scan("Qué mierda", None).severity, None);
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{current_window, RateLimitHit};
+
+/// Counters are swept once the map grows past this many keys
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Rate limit counters kept in the process. Cheap, but each instance of the
+/// API counts on its own, so use the Postgres store when running several.
+#[derive(Debug, Default)]
+pub struct InMemoryRateLimitStore {
+    counters: Mutex<HashMap<String, RateLimitHit>>,
+}
+
+impl InMemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hit(&self, key: &str, window: Duration) -> RateLimitHit {
+        let (_, resets_at) = current_window(window);
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        if counters.len() >= SWEEP_THRESHOLD {
+            sweep(&mut counters);
+        }
+
+        let counter = counters
+            .entry(key.to_string())
+            .or_insert(RateLimitHit { hits: 0, resets_at });
+        if counter.resets_at != resets_at {
+            *counter = RateLimitHit { hits: 0, resets_at };
+        }
+        counter.hits += 1;
+
+        counter.clone()
+    }
+
+    /// Forgets counters whose window is over, returning how many there were
+    pub fn prune(&self) -> usize {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        sweep(&mut counters)
+    }
+}
+
+fn sweep(counters: &mut HashMap<String, RateLimitHit>) -> usize {
+    let before = counters.len();
+    let now = chrono::Utc::now();
+    counters.retain(|_, counter| counter.resets_at > now);
+    before - counters.len()
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A single IP address or a CIDR range, such as `10.0.0.0/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(network).into(),
+                u32::from(ip).into(),
+                self.prefix_len,
+                32,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(network.into(), ip.into(), self.prefix_len, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: u128, ip: u128, prefix_len: u8, bits: u8) -> bool {
+    let shift = u32::from(bits - prefix_len);
+    network.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not an IP address or CIDR range", value);
+
+        let (address, prefix_len) = match value.trim().split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (value.trim(), None),
+        };
+        let address = IpAddr::from_str(address)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
@@ -0,0 +1,27 @@
+mod in_memory_store;
+pub use in_memory_store::*;
+
+mod ip_network;
+pub use ip_network::*;
+
+use std::time::Duration;
+
+/// A client's hits in the current rate limit window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitHit {
+    pub hits: u32,
+    pub resets_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Start and end of the fixed window of length `window` we are in. Windows are
+/// aligned on the epoch, so every store agrees on them.
+pub fn current_window(
+    window: Duration,
+) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+    let window_secs = window.as_secs().max(1) as i64;
+    let now = chrono::Utc::now().timestamp();
+    let start = now - now.rem_euclid(window_secs);
+
+    let at = |timestamp| chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+    (at(start), at(start + window_secs))
+}
@@ -1,63 +1,290 @@
 use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_s3::config::{BehaviorVersion, Region};
+use aws_sdk_s3::config::{BehaviorVersion, ProvideCredentials, Region, SharedCredentialsProvider};
 use aws_sdk_s3::presigning::PresigningConfig;
-use aws_sdk_s3::types::ObjectCannedAcl;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::ChecksumMode;
 use aws_sdk_s3::Client as S3Client;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::error::ApiError;
+use crate::utils::bucket::{BucketObject, ObjectMetadata, PresignedPost, UploadPolicy};
 use crate::utils::Config;
 
+type HmacSha256 = Hmac<Sha256>;
+
 #[derive(Debug, Clone)]
 pub struct S3Repository {
     pub client: Arc<S3Client>,
     pub bucket: String,
+    /// Base URL objects are identified by, without a trailing slash
+    pub public_url_base: String,
+    /// URL of the bucket itself, where form uploads are posted
+    pub bucket_url: String,
+    region: Region,
+    credentials: Option<SharedCredentialsProvider>,
 }
 
 impl S3Repository {
     pub async fn new() -> Result<Self, ApiError> {
         let config = Config::from_env();
 
+        let bucket = config
+            .s3_bucket
+            .ok_or_else(|| ApiError::UnexpectedError("S3_BUCKET must be set".to_string()))?;
+
         let region_provider =
-            RegionProviderChain::first_try(Region::new(config.aws_region.clone()))
+            RegionProviderChain::first_try(config.aws_region.clone().map(Region::new))
                 .or_default_provider()
                 .or_else(Region::new("us-east-1"));
+        let region = region_provider.region().await.ok_or_else(|| {
+            ApiError::UnexpectedError("Failed to determine AWS region".to_string())
+        })?;
+
+        let shared_config = aws_config::defaults(BehaviorVersion::latest())
+            .region(region.clone())
+            .load()
+            .await;
+
+        // S3-compatible services (MinIO, R2...) are reached through a custom endpoint,
+        // usually with the bucket in the path instead of the host name
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&shared_config)
+            .force_path_style(config.s3_force_path_style);
+        if let Some(endpoint) = &config.s3_endpoint {
+            s3_config = s3_config.endpoint_url(endpoint);
+        }
 
-        let s3_config = aws_sdk_s3::Config::builder()
-            .behavior_version(BehaviorVersion::latest())
-            .region(region_provider.region().await.ok_or_else(|| {
-                ApiError::UnexpectedError("Failed to determine AWS region".to_string())
-            })?)
-            .build();
+        let client = S3Client::from_conf(s3_config.build());
 
-        let client = S3Client::from_conf(s3_config);
+        let bucket_url = match &config.s3_endpoint {
+            Some(endpoint) => endpoint_url_base(endpoint, &bucket, config.s3_force_path_style),
+            None => format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+        };
+        let public_url_base = match &config.s3_public_url {
+            Some(public_url) => public_url.trim_end_matches('/').to_string(),
+            None => bucket_url.clone(),
+        };
 
         Ok(Self {
             client: Arc::new(client),
-            bucket: config.s3_bucket,
+            bucket,
+            public_url_base,
+            bucket_url,
+            region,
+            credentials: shared_config.credentials_provider(),
         })
     }
 
-    pub async fn post_presigned_url(
+    pub fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url_base, key)
+    }
+
+    /// Presigns a GET of a private object, valid for `expires_in` from `signed_at`
+    pub async fn presigned_get(
         &self,
         key: &str,
-        duration: Duration,
+        signed_at: chrono::DateTime<chrono::Utc>,
+        expires_in: Duration,
     ) -> Result<String, ApiError> {
-        let presigned_req = self
+        let presigning_config = PresigningConfig::builder()
+            .start_time(signed_at.into())
+            .expires_in(expires_in)
+            .build()
+            .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(presigning_config)
+            .await
+            .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+        Ok(request.uri().to_string())
+    }
+
+    /// Signs a POST policy (SigV4) letting a browser upload a single object
+    /// with the given content type and size, as multipart form data
+    pub async fn presigned_post(
+        &self,
+        key: &str,
+        policy: &UploadPolicy,
+    ) -> Result<PresignedPost, ApiError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ApiError::UnexpectedError("No AWS credentials configured".to_string()))?
+            .provide_credentials()
+            .await
+            .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+
+        let now = chrono::Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let expiration = now
+            + chrono::Duration::from_std(policy.expires_in)
+                .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+        let credential = format!(
+            "{}/{}/{}/s3/aws4_request",
+            credentials.access_key_id(),
+            date,
+            self.region
+        );
+
+        let mut fields = BTreeMap::from([
+            ("key".to_string(), key.to_string()),
+            ("Content-Type".to_string(), policy.content_type.clone()),
+            (
+                "x-amz-algorithm".to_string(),
+                "AWS4-HMAC-SHA256".to_string(),
+            ),
+            ("x-amz-credential".to_string(), credential),
+            ("x-amz-date".to_string(), amz_date),
+        ]);
+        if let Some(token) = credentials.session_token() {
+            fields.insert("x-amz-security-token".to_string(), token.to_string());
+        }
+
+        let mut conditions = vec![
+            serde_json::json!({ "bucket": self.bucket }),
+            serde_json::json!(["content-length-range", 1, policy.max_size_bytes]),
+        ];
+        conditions.extend(
+            fields
+                .iter()
+                .map(|(name, value)| serde_json::json!({ name: value })),
+        );
+        let policy_document = serde_json::json!({
+            "expiration": expiration.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+            "conditions": conditions,
+        });
+        let encoded_policy =
+            base64::engine::general_purpose::STANDARD.encode(policy_document.to_string());
+
+        let signing_key = [date.as_str(), self.region.as_ref(), "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", credentials.secret_access_key()).into_bytes(),
+                |key, part| hmac_sha256(&key, part.as_bytes()),
+            );
+        fields.insert(
+            "x-amz-signature".to_string(),
+            hex::encode(hmac_sha256(&signing_key, encoded_policy.as_bytes())),
+        );
+        fields.insert("policy".to_string(), encoded_policy);
+
+        Ok(PresignedPost {
+            url: self.bucket_url.clone(),
+            fields,
+        })
+    }
+
+    pub async fn head_object(&self, key: &str) -> Result<ObjectMetadata, ApiError> {
+        let output = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .checksum_mode(ChecksumMode::Enabled)
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service_error) if service_error.is_not_found() => {
+                    ApiError::NotFound(format!("Object {} not found", key))
+                }
+                _ => ApiError::UnexpectedError(e.to_string()),
+            })?;
+
+        // S3 only reports a SHA-256 when the upload sent one; multipart uploads
+        // report a checksum of checksums ("...-N"), which does not decode
+        let sha256 = output
+            .checksum_sha256()
+            .and_then(|checksum| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(checksum)
+                    .ok()
+            })
+            .map(hex::encode);
+
+        Ok(ObjectMetadata {
+            content_type: output.content_type().map(str::to_string),
+            size_bytes: output.content_length(),
+            sha256,
+        })
+    }
+
+    pub async fn get_object(&self, key: &str) -> Result<Vec<u8>, ApiError> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service_error) if service_error.is_no_such_key() => {
+                    ApiError::NotFound(format!("Object {} not found", key))
+                }
+                _ => ApiError::UnexpectedError(e.to_string()),
+            })?;
+
+        let body = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+        Ok(body.into_bytes().to_vec())
+    }
+
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, ApiError> {
+        let mut pages = self
             .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
+            objects.extend(page.contents().iter().filter_map(|object| {
+                let last_modified = object.last_modified()?;
+                Some(BucketObject {
+                    key: object.key()?.to_string(),
+                    last_modified: chrono::DateTime::from_timestamp(
+                        last_modified.secs(),
+                        last_modified.subsec_nanos(),
+                    )?,
+                    size_bytes: object.size().unwrap_or_default(),
+                })
+            }));
+        }
+
+        Ok(objects)
+    }
+
+    pub async fn put_object(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), ApiError> {
+        self.client
             .put_object()
             .bucket(&self.bucket)
             .key(key)
-            .acl(ObjectCannedAcl::PublicRead)
-            .presigned(
-                PresigningConfig::expires_in(duration)
-                    .map_err(|e| ApiError::UnexpectedError(e.to_string()))?,
-            )
+            .content_type(content_type)
+            .body(ByteStream::from(bytes))
+            .send()
             .await
             .map_err(|e| ApiError::UnexpectedError(e.to_string()))?;
 
-        Ok(presigned_req.uri().to_string())
+        Ok(())
     }
 
     pub async fn delete_object(&self, key: &str) -> Result<(), ApiError> {
@@ -72,3 +299,22 @@ impl S3Repository {
         Ok(())
     }
 }
+
+/// Public base URL of a bucket served by a custom S3 endpoint
+fn endpoint_url_base(endpoint: &str, bucket: &str, path_style: bool) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if path_style {
+        return format!("{}/{}", endpoint, bucket);
+    }
+
+    match endpoint.split_once("://") {
+        Some((scheme, host)) => format!("{}://{}.{}", scheme, bucket, host),
+        None => format!("https://{}.{}", bucket, endpoint),
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}